@@ -0,0 +1,367 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::spm_image::SpmImage;
+use crate::utils::length_to_nm;
+
+/// Image imported from a plain text matrix or from `x y z` triplets
+#[derive(Debug)]
+pub struct AsciiImage {
+    pub filepath: PathBuf,
+    pub layout: AsciiLayout,
+    /// `None` for whitespace
+    pub delimiter: Option<char>,
+    /// Lines before the data
+    pub header: Vec<String>,
+    /// Whether XYZ data was resampled because it is not on a regular grid
+    pub resampled: bool,
+    pub img_data: SpmImage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AsciiLayout {
+    /// One line of the image per row, first row at the top
+    Matrix,
+    /// One point per row
+    Xyz,
+}
+
+/// Overrides for what is detected from the file
+#[derive(Debug, Clone, Default)]
+pub struct AsciiOptions {
+    pub layout: Option<AsciiLayout>,
+    /// Size in nm, otherwise from the header or XYZ coordinates, or 1 nm per pixel.
+    /// XYZ points are the centres of pixels, so the size from coordinates is their
+    /// range plus one pixel, whether the points are on a grid or resampled.
+    pub xsize: Option<f64>,
+    /// Size in nm, like `xsize`
+    pub ysize: Option<f64>,
+    /// Length unit of XYZ coordinates, `nm` if empty. Other units are an error.
+    pub xy_unit: String,
+    pub z_unit: String,
+    /// Grid size for resampled XYZ data, otherwise about one pixel per point
+    pub xres: Option<usize>,
+    /// Grid size for resampled XYZ data, otherwise about one pixel per point
+    pub yres: Option<usize>,
+}
+
+const DELIMITERS: [char; 3] = [',', ';', '\t'];
+const COMMENT_PREFIXES: [&str; 3] = ["#", "%", "//"];
+
+fn split_line(line: &str, delimiter: Option<char>) -> Vec<&str> {
+    match delimiter {
+        Some(d) => line.split(d).map(str::trim).collect(),
+        None => line.split_whitespace().collect(),
+    }
+}
+
+fn parse_values(line: &str, delimiter: Option<char>) -> Option<Vec<f64>> {
+    let values = split_line(line.trim(), delimiter)
+        .into_iter()
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<f64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    (!values.is_empty()).then_some(values)
+}
+
+/// First delimiter with which the line splits into more than one number
+fn detect_delimiter(line: &str) -> Option<char> {
+    DELIMITERS
+        .into_iter()
+        .find(|&d| line.contains(d) && parse_values(line, Some(d)).is_some_and(|v| v.len() > 1))
+}
+
+/// Factor to nm of a length unit, `nm` if empty
+fn unit_to_nm(unit: &str) -> Result<f64> {
+    match unit {
+        "" => Ok(1.0),
+        unit => length_to_nm(unit).ok_or_else(|| anyhow!("Unknown length unit {}", unit)),
+    }
+}
+
+/// Size in nm from header lines like `# Width: 10 nm` or `XReal = 1e-8 m`, in nm
+/// without unit
+fn header_size(header: &[String], keys: &[&str]) -> Result<Option<f64>> {
+    let size = header.iter().find_map(|line| {
+        let line = COMMENT_PREFIXES
+            .iter()
+            .fold(line.trim(), |l, p| l.trim_start_matches(p))
+            .trim();
+        let (key, value) = line.split_once(':').or_else(|| line.split_once('='))?;
+        if !keys.iter().any(|k| key.trim().eq_ignore_ascii_case(k)) {
+            return None;
+        }
+        let mut parts = value.split_whitespace();
+        let number: f64 = parts.next()?.parse().ok()?;
+        Some((number, parts.next().unwrap_or_default()))
+    });
+    size.map(|(number, unit)| Ok(number * unit_to_nm(unit)?))
+        .transpose()
+}
+
+/// Sorted distinct values, equal within a fraction of the full range
+fn distinct_values(values: &[f64]) -> Vec<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let range = sorted.last().unwrap() - sorted.first().unwrap();
+    let tolerance = range * 1e-6;
+    sorted.dedup_by(|a, b| (*a - *b).abs() <= tolerance);
+    sorted
+}
+
+/// Index of the value in a regular grid from `min` with `step`
+fn grid_index(value: f64, min: f64, step: f64, res: usize) -> usize {
+    if step == 0.0 {
+        return 0;
+    }
+    (((value - min) / step).round().max(0.0) as usize).min(res - 1)
+}
+
+/// Averages the points in each pixel and fills empty pixels from their nearest
+/// filled pixels, the first row is the top of the image at maximum y. The pixel
+/// centres of the first and last column and row are at the minimum and maximum.
+fn resample(points: &[[f64; 3]], xres: usize, yres: usize) -> Vec<f64> {
+    let (xmin, xmax, ymin, ymax) = bounds(points);
+    let xstep = pixel_step(xmin, xmax, xres);
+    let ystep = pixel_step(ymin, ymax, yres);
+    let mut sums = vec![0.0; xres * yres];
+    let mut counts = vec![0usize; xres * yres];
+    for [x, y, z] in points {
+        let col = grid_index(*x, xmin, xstep, xres);
+        let row = yres - 1 - grid_index(*y, ymin, ystep, yres);
+        sums[row * xres + col] += z;
+        counts[row * xres + col] += 1;
+    }
+    let mut img: Vec<Option<f64>> = sums
+        .iter()
+        .zip(counts.iter())
+        .map(|(s, &c)| (c > 0).then(|| s / c as f64))
+        .collect();
+
+    // Breadth-first from the filled pixels, each empty pixel is the mean of its
+    // neighbours one step closer to a filled pixel
+    let mut distance: Vec<Option<usize>> = img.iter().map(|v| v.map(|_| 0)).collect();
+    let mut queue: VecDeque<usize> = (0..img.len()).filter(|&i| img[i].is_some()).collect();
+    let neighbours = |i: usize| {
+        let (row, col) = (i / xres, i % xres);
+        [(0, -1), (0, 1), (-1, 0), (1, 0)]
+            .into_iter()
+            .filter_map(move |(dr, dc)| {
+                let r = row.checked_add_signed(dr).filter(|&r| r < yres)?;
+                let c = col.checked_add_signed(dc).filter(|&c| c < xres)?;
+                Some(r * xres + c)
+            })
+    };
+    while let Some(i) = queue.pop_front() {
+        let d = distance[i].unwrap();
+        if d > 0 {
+            let closer: Vec<f64> = neighbours(i)
+                .filter(|&n| distance[n] == Some(d - 1))
+                .filter_map(|n| img[n])
+                .collect();
+            img[i] = Some(closer.iter().sum::<f64>() / closer.len() as f64);
+        }
+        for n in neighbours(i) {
+            if distance[n].is_none() {
+                distance[n] = Some(d + 1);
+                queue.push_back(n);
+            }
+        }
+    }
+    img.into_iter().map(Option::unwrap).collect()
+}
+
+fn bounds(points: &[[f64; 3]]) -> (f64, f64, f64, f64) {
+    points.iter().fold(
+        (
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ),
+        |(xmin, xmax, ymin, ymax), [x, y, _]| {
+            (xmin.min(*x), xmax.max(*x), ymin.min(*y), ymax.max(*y))
+        },
+    )
+}
+
+/// Distance of `res` pixel centres from `min` to `max`
+fn pixel_step(min: f64, max: f64, res: usize) -> f64 {
+    if res > 1 {
+        (max - min) / (res - 1) as f64
+    } else {
+        0.0
+    }
+}
+
+/// Size of `res` pixels centred from `min` to `max`, the range for a single pixel and 1
+/// if all coordinates are equal
+fn grid_size(min: f64, max: f64, res: usize) -> f64 {
+    if max <= min {
+        1.0
+    } else if res > 1 {
+        pixel_step(min, max, res) * res as f64
+    } else {
+        max - min
+    }
+}
+
+struct Grid {
+    xres: usize,
+    yres: usize,
+    xsize: f64,
+    ysize: f64,
+    data: Vec<f64>,
+    resampled: bool,
+}
+
+/// Image and size in units of the coordinates of XYZ data, placed directly if the
+/// points form a regular grid and resampled otherwise
+fn xyz_to_grid(points: &[[f64; 3]], options: &AsciiOptions) -> Result<Grid> {
+    let xs: Vec<f64> = points.iter().map(|p| p[0]).collect();
+    let ys: Vec<f64> = points.iter().map(|p| p[1]).collect();
+    let distinct_x = distinct_values(&xs);
+    let distinct_y = distinct_values(&ys);
+    let (xmin, xmax, ymin, ymax) = bounds(points);
+
+    let (xres, yres) = (distinct_x.len(), distinct_y.len());
+    let regular = xres * yres == points.len() && options.xres.is_none() && options.yres.is_none();
+    if regular {
+        let xstep = pixel_step(xmin, xmax, xres);
+        let ystep = pixel_step(ymin, ymax, yres);
+        let mut img = vec![f64::NAN; xres * yres];
+        for [x, y, z] in points {
+            let col = grid_index(*x, xmin, xstep, xres);
+            let row = yres - 1 - grid_index(*y, ymin, ystep, yres);
+            img[row * xres + col] = *z;
+        }
+        // Unequal steps leave pixels empty and are resampled below
+        if !img.iter().any(|v| v.is_nan()) {
+            return Ok(Grid {
+                xres,
+                yres,
+                xsize: grid_size(xmin, xmax, xres),
+                ysize: grid_size(ymin, ymax, yres),
+                data: img,
+                resampled: false,
+            });
+        }
+    }
+
+    let aspect = if xmax > xmin && ymax > ymin {
+        (ymax - ymin) / (xmax - xmin)
+    } else {
+        1.0
+    };
+    let xres = options
+        .xres
+        .unwrap_or(((points.len() as f64 / aspect).sqrt().round() as usize).max(1));
+    let yres = options
+        .yres
+        .unwrap_or(((xres as f64 * aspect).round() as usize).max(1));
+    Ok(Grid {
+        xres,
+        yres,
+        xsize: grid_size(xmin, xmax, xres),
+        ysize: grid_size(ymin, ymax, yres),
+        data: resample(points, xres, yres),
+        resampled: true,
+    })
+}
+
+pub fn read_ascii(filename: &str, options: &AsciiOptions) -> Result<AsciiImage> {
+    if options.xres == Some(0) || options.yres == Some(0) {
+        return Err(anyhow!("Grid size must be at least 1x1"));
+    }
+    let bytes = fs::read(filename)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+
+    // Header lines are the ones before the first line of numbers
+    let first_data = lines
+        .iter()
+        .position(|l| {
+            !COMMENT_PREFIXES.iter().any(|p| l.trim().starts_with(p))
+                && parse_values(l, detect_delimiter(l)).is_some()
+        })
+        .ok_or_else(|| anyhow!("No numeric data in {}", filename))?;
+    let header: Vec<String> = lines[..first_data].iter().map(|l| l.to_string()).collect();
+    let delimiter = detect_delimiter(lines[first_data]);
+
+    let mut rows: Vec<Vec<f64>> = Vec::new();
+    for line in &lines[first_data..] {
+        if COMMENT_PREFIXES.iter().any(|p| line.trim().starts_with(p)) {
+            continue;
+        }
+        let values = parse_values(line, delimiter)
+            .ok_or_else(|| anyhow!("Invalid data in line `{}`", line))?;
+        if rows
+            .first()
+            .is_some_and(|first| first.len() != values.len())
+        {
+            return Err(anyhow!("Inconsistent number of columns in line `{}`", line));
+        }
+        rows.push(values);
+    }
+
+    let num_columns = rows[0].len();
+    let layout = options
+        .layout
+        .unwrap_or(if num_columns == 3 && rows.len() > 3 {
+            AsciiLayout::Xyz
+        } else {
+            AsciiLayout::Matrix
+        });
+
+    let grid = match layout {
+        AsciiLayout::Matrix => {
+            let (xres, yres) = (num_columns, rows.len());
+            let xsize = header_size(&header, &["Width", "X size", "XReal"])?;
+            let ysize = header_size(&header, &["Height", "Y size", "YReal"])?;
+            Grid {
+                xres,
+                yres,
+                xsize: xsize.unwrap_or(xres as f64),
+                ysize: ysize.unwrap_or(yres as f64),
+                data: rows.concat(),
+                resampled: false,
+            }
+        }
+        AsciiLayout::Xyz => {
+            if num_columns < 3 {
+                return Err(anyhow!("Expected x y z columns, got {}", num_columns));
+            }
+            let points: Vec<[f64; 3]> = rows.iter().map(|r| [r[0], r[1], r[2]]).collect();
+            let factor = unit_to_nm(&options.xy_unit)?;
+            let grid = xyz_to_grid(&points, options)?;
+            Grid {
+                xsize: grid.xsize * factor,
+                ysize: grid.ysize * factor,
+                ..grid
+            }
+        }
+    };
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+
+    Ok(AsciiImage {
+        img_data: SpmImage {
+            img_id: basename.to_string(),
+            xres: grid.xres,
+            yres: grid.yres,
+            xsize: options.xsize.unwrap_or(grid.xsize),
+            ysize: options.ysize.unwrap_or(grid.ysize),
+            z_unit: options.z_unit.clone(),
+            img_data: grid.data,
+        },
+        filepath,
+        layout,
+        delimiter,
+        header,
+        resampled: grid.resampled,
+    })
+}
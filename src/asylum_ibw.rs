@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+use crate::igor_ibw::read_ibw;
+use crate::spm_image::flip_img_data;
+use crate::spm_image::SpmImage;
+
+/// Asylum Research AFM image, saved as 3D Igor wave with one layer per channel
+#[derive(Debug)]
+pub struct AsylumImage {
+    pub filepath: PathBuf,
+    pub img_id: String,
+    pub datetime: DateTime<Utc>,
+    /// Size in pixels
+    pub xres: usize,
+    /// Size in pixels
+    pub yres: usize,
+    /// Size in nm
+    pub xsize: f64,
+    /// Size in nm
+    pub ysize: f64,
+    /// Offset in nm
+    pub xoffset: f64,
+    /// Offset in nm
+    pub yoffset: f64,
+    /// Rotation in degrees
+    pub scan_angle: f64,
+    /// Scan rate in Hz (lines per second)
+    pub scan_rate: f64,
+    pub set_point: f64,
+    pub imaging_mode: String,
+    /// All `Key: value` pairs of the wave note
+    pub metadata: BTreeMap<String, NoteValue>,
+    pub channels: Vec<SpmImage>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NoteValue {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl NoteValue {
+    fn parse(value: &str) -> Self {
+        if let Ok(i) = value.parse::<i64>() {
+            NoteValue::Int(i)
+        } else if let Ok(f) = value.parse::<f64>() {
+            NoteValue::Float(f)
+        } else {
+            NoteValue::Text(value.to_string())
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            NoteValue::Int(i) => Some(*i as f64),
+            NoteValue::Float(f) => Some(*f),
+            NoteValue::Text(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for NoteValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoteValue::Int(i) => write!(f, "{}", i),
+            NoteValue::Float(x) => write!(f, "{}", x),
+            NoteValue::Text(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Parses the `Key: value` lines of an Asylum Research wave note
+pub fn parse_note(note: &str) -> BTreeMap<String, NoteValue> {
+    note.lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), NoteValue::parse(value.trim())))
+        .collect()
+}
+
+pub fn read_asylum_ibw(filename: &str) -> Result<AsylumImage> {
+    let ibw = read_ibw(filename)?;
+    let metadata = parse_note(&ibw.note);
+
+    let shape = ibw.shape();
+    if shape.len() < 2 {
+        return Err(anyhow!("Not an image wave: {:?}", shape));
+    }
+    let xres = shape[0];
+    let yres = shape[1];
+    let num_layers = shape.get(2).copied().unwrap_or(1);
+
+    let note_f64 = |key: &str| metadata.get(key).and_then(NoteValue::as_f64);
+
+    // Scan sizes from the note, falling back to the wave scaling, in m
+    let xsize = note_f64("FastScanSize").unwrap_or(ibw.x_step[0] * xres as f64) * 1e9;
+    let ysize = note_f64("SlowScanSize").unwrap_or(ibw.x_step[1] * yres as f64) * 1e9;
+    let xoffset = note_f64("XOffset").unwrap_or(ibw.x_start[0]) * 1e9;
+    let yoffset = note_f64("YOffset").unwrap_or(ibw.x_start[1]) * 1e9;
+    let scan_angle = note_f64("ScanAngle").unwrap_or_default();
+    let scan_rate = note_f64("ScanRate").unwrap_or_default();
+    let set_point = note_f64("SetPoint").unwrap_or_default();
+    let imaging_mode = metadata
+        .get("ImagingModeStr")
+        .map(|mode| mode.to_string())
+        .unwrap_or_default();
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+    let img_id = basename.to_string();
+
+    let labels = ibw.index_labels(2);
+    let data = ibw.data.to_f64();
+    let layer_len = xres * yres;
+
+    // Igor stores the layers in column-major order, so each layer is a sequence of
+    // scan lines starting at the bottom of the image
+    let channels = (0..num_layers)
+        .map(|layer| {
+            let name = labels
+                .get(layer)
+                .filter(|label| !label.is_empty())
+                .cloned()
+                .unwrap_or_else(|| format!("Channel{}", layer));
+            let layer_data = data[layer * layer_len..(layer + 1) * layer_len].to_vec();
+            SpmImage {
+                img_id: format!("{}_{}", basename, name),
+                xsize,
+                ysize,
+                xres,
+                yres,
+                z_unit: channel_unit(&name).to_string(),
+                img_data: flip_img_data(layer_data, xres as u32, yres as u32),
+            }
+        })
+        .collect();
+
+    Ok(AsylumImage {
+        filepath,
+        img_id,
+        datetime: ibw.creation_date,
+        xres,
+        yres,
+        xsize,
+        ysize,
+        xoffset,
+        yoffset,
+        scan_angle,
+        scan_rate,
+        set_point,
+        imaging_mode,
+        metadata,
+        channels,
+    })
+}
+
+/// Asylum Research does not store per-layer units, they follow from the channel name
+fn channel_unit(channel: &str) -> &str {
+    match channel {
+        n if n.starts_with("Phase") => "deg",
+        n if n.starts_with("Current") => "A",
+        n if n.starts_with("Potential") || n.starts_with("UserIn") || n.starts_with("Bias") => "V",
+        n if n.starts_with("Frequency") => "Hz",
+        _ => "m",
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use flate2::read::ZlibDecoder;
+
+use crate::spm_image::SpmImage;
+
+/// Createc image (`.dat`), a text header of fixed size followed by the
+/// zlib-compressed float32 data of all channels
+#[derive(Debug)]
+pub struct CreatecImage {
+    pub filepath: PathBuf,
+    pub img_id: String,
+    /// Size in pixels
+    pub xres: usize,
+    /// Size in pixels
+    pub yres: usize,
+    /// Size in nm
+    pub xsize: f64,
+    /// Size in nm
+    pub ysize: f64,
+    /// Bias in mV
+    pub bias: f64,
+    /// Current setpoint in A
+    pub current: f64,
+    /// All `Key=value` pairs of the header
+    pub header: BTreeMap<String, String>,
+    /// Forward and, if recorded, backward image of each channel
+    pub channels: Vec<SpmImage>,
+}
+
+// The header always takes the first 16 kB of the file
+const HEADER_SIZE: usize = 16384;
+// The decompressed data starts with one unused float
+const DATA_OFFSET: usize = 4;
+const COMPRESSED_FLOAT32: &str = "[Paramco32]";
+
+// Voltage of the 16 bit ADCs, +-10 V
+pub(crate) const ADC_TO_VOLT: f64 = 10.0 / 32768.0;
+
+/// Keys of older software versions repeat the name, e.g. `Num.X / Num.X=256`
+pub(crate) fn parse_createc_header(header: &str) -> BTreeMap<String, String> {
+    header
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let key = key.rsplit(" / ").next().unwrap_or(key);
+            (key.trim().to_string(), value.trim().to_string())
+        })
+        .collect()
+}
+
+pub(crate) fn header_value(header: &BTreeMap<String, String>, key: &str) -> Result<f64> {
+    header
+        .get(key)
+        .ok_or_else(|| anyhow!("Missing {} in header", key))?
+        .parse::<f64>()
+        .map_err(|e| anyhow!("Invalid {}: {}", key, e))
+}
+
+/// Height of the z piezo in m per DAC unit
+pub(crate) fn z_calibration(header: &BTreeMap<String, String>) -> Result<f64> {
+    let gain = header_value(header, "GainZ").unwrap_or(1.0);
+    Ok(header_value(header, "Dacto[A]z")? * gain * 1e-10)
+}
+
+/// Current in A per ADC unit, from the gain of the preamplifier in V/A
+pub(crate) fn current_calibration(header: &BTreeMap<String, String>) -> f64 {
+    let gain = header_value(header, "Gainpre 10^").unwrap_or(9.0);
+    ADC_TO_VOLT / 10_f64.powf(gain)
+}
+
+/// Name of the channel with the given bit of `Channelselectval`
+fn channel_name(bit: usize) -> String {
+    match bit {
+        0 => "Topography".to_string(),
+        1 => "Current".to_string(),
+        n => format!("ADC{}", n - 1),
+    }
+}
+
+pub fn read_createc_dat(filename: &str) -> Result<CreatecImage> {
+    let bytes = fs::read(filename)?;
+    if bytes.len() < HEADER_SIZE {
+        return Err(anyhow!("File shorter than the header: {}", filename));
+    }
+
+    let header_text = String::from_utf8_lossy(&bytes[..HEADER_SIZE]);
+    if !header_text.starts_with(COMPRESSED_FLOAT32) {
+        return Err(anyhow!(
+            "Only compressed float data ({}) is supported: {}",
+            COMPRESSED_FLOAT32,
+            filename
+        ));
+    }
+    let header = parse_createc_header(header_text.trim_end_matches(char::from(0)));
+
+    let get = |key: &str| header_value(&header, key);
+
+    let xres = get("Num.X")? as usize;
+    let yres = get("Num.Y")? as usize;
+    let xsize = get("Length x[A]")? / 10.0; // in nm
+    let ysize = get("Length y[A]")? / 10.0; // in nm
+    let bias = get("BiasVolt.[mV]").unwrap_or_default();
+    let current = get("Current[A]").unwrap_or_default();
+    let num_channels = get("Channels")? as usize;
+
+    let z_calibration = z_calibration(&header)?;
+    let current_calibration = current_calibration(&header);
+
+    // Without `Channelselectval` the channels are topography, current, ADC1, ...
+    let select = get("Channelselectval")
+        .map(|x| x as u32)
+        .unwrap_or(u32::MAX);
+    let mut selected: Vec<usize> = (0..32).filter(|bit| select & (1 << bit) != 0).collect();
+    selected.truncate(num_channels);
+    // `Channels` counts forward and backward images
+    let backward = num_channels == 2 * selected.len();
+
+    let mut data = Vec::new();
+    ZlibDecoder::new(&bytes[HEADER_SIZE..]).read_to_end(&mut data)?;
+    let frame_len = xres * yres;
+    if frame_len == 0 || data.len() < DATA_OFFSET + num_channels * frame_len * 4 {
+        return Err(anyhow!(
+            "Expected {} channels of {}x{} pixels, got {} bytes",
+            num_channels,
+            xres,
+            yres,
+            data.len()
+        ));
+    }
+    let values: Vec<f64> = data[DATA_OFFSET..]
+        .chunks_exact(4)
+        .map(|x| f64::from(f32::from_le_bytes([x[0], x[1], x[2], x[3]])))
+        .collect();
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+    let img_id = basename.to_string();
+
+    let directions: &[&str] = if backward { &["fwd", "bwd"] } else { &["fwd"] };
+    let mut channels = Vec::with_capacity(num_channels);
+    let mut frames = values.chunks_exact(frame_len);
+    for direction in directions {
+        for bit in selected.iter() {
+            let name = channel_name(*bit);
+            let (unit, factor) = match bit {
+                0 => ("m", z_calibration),
+                1 => ("A", current_calibration),
+                _ => ("V", ADC_TO_VOLT),
+            };
+            let mut img_data: Vec<f64> = frames
+                .next()
+                .unwrap_or_default()
+                .iter()
+                .map(|x| x * factor)
+                .collect();
+            // Backward lines are stored in the order they were scanned, right to left
+            if *direction == "bwd" {
+                img_data
+                    .chunks_exact_mut(xres)
+                    .for_each(|line| line.reverse());
+            }
+            channels.push(SpmImage {
+                img_id: format!("{}_{}_{}", img_id, name, direction),
+                xres,
+                yres,
+                xsize,
+                ysize,
+                z_unit: unit.to_string(),
+                img_data,
+            });
+        }
+    }
+
+    Ok(CreatecImage {
+        filepath,
+        img_id,
+        xres,
+        yres,
+        xsize,
+        ysize,
+        bias,
+        current,
+        header,
+        channels,
+    })
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::createc_dat::{
+    current_calibration, header_value, parse_createc_header, z_calibration, ADC_TO_VOLT,
+};
+use crate::spm_spectrum::{SpectrumChannel, SpmSpectrum};
+
+/// Createc point spectroscopy (`.VERT`), the parameter header followed by
+/// whitespace separated columns
+#[derive(Debug)]
+pub struct CreatecSpectrum {
+    pub filepath: PathBuf,
+    /// All `Key=value` pairs of the header
+    pub header: BTreeMap<String, String>,
+    pub num_points: usize,
+    /// Tip position in DAC units of the xy piezo
+    pub xpos_dac: f64,
+    /// Tip position in DAC units of the xy piezo
+    pub ypos_dac: f64,
+    /// Bias in mV as x axis, the other columns calibrated
+    pub spectrum: SpmSpectrum,
+}
+
+const DATA_SECTION: &str = "DATA";
+
+// Columns of files written without column names
+const DEFAULT_COLUMNS: [&str; 4] = ["idx", "V", "Z", "I"];
+
+/// Name, unit and factor to physical units of a column
+fn calibrate_column(
+    column: &str,
+    header: &BTreeMap<String, String>,
+) -> Result<(String, String, f64)> {
+    Ok(match column {
+        "V" => ("Bias".to_string(), "mV".to_string(), 1.0),
+        "Z" => ("Z".to_string(), "m".to_string(), z_calibration(header)?),
+        "I" => (
+            "Current".to_string(),
+            "A".to_string(),
+            current_calibration(header),
+        ),
+        name => (name.to_string(), "V".to_string(), ADC_TO_VOLT),
+    })
+}
+
+pub fn read_createc_vert(filename: &str) -> Result<CreatecSpectrum> {
+    let bytes = fs::read(filename)?;
+    let text = String::from_utf8_lossy(&bytes);
+
+    let (header_text, data_text) = text
+        .split_once(&format!("\n{}", DATA_SECTION))
+        .ok_or_else(|| anyhow!("No {} section in {}", DATA_SECTION, filename))?;
+    let header = parse_createc_header(header_text);
+    let mut lines = data_text.lines().skip(1).filter(|l| !l.trim().is_empty());
+
+    // Number of points and tip position in DAC units
+    let position: Vec<f64> = lines
+        .next()
+        .ok_or_else(|| anyhow!("No tip position in {}", filename))?
+        .split_whitespace()
+        .map(|x| x.parse::<f64>())
+        .collect::<Result<_, _>>()?;
+    let (num_points, xpos_dac, ypos_dac) = match position[..] {
+        [num_points, x, y, ..] => (num_points as usize, x, y),
+        _ => return Err(anyhow!("Invalid tip position in {}", filename)),
+    };
+
+    let mut lines = lines.peekable();
+    let mut column_names: Vec<String> = DEFAULT_COLUMNS.iter().map(|c| c.to_string()).collect();
+    if let Some(line) = lines.peek() {
+        if line
+            .split_whitespace()
+            .any(|word| word.parse::<f64>().is_err())
+        {
+            column_names = line.split_whitespace().map(str::to_string).collect();
+            lines.next();
+        }
+    }
+
+    let mut columns: Vec<Vec<f64>> = Vec::new();
+    for line in lines {
+        let values: Vec<f64> = line
+            .split_whitespace()
+            .map(|x| x.parse::<f64>())
+            .collect::<Result<_, _>>()?;
+        if columns.is_empty() {
+            columns = vec![Vec::with_capacity(num_points); values.len()];
+        }
+        if values.len() != columns.len() {
+            return Err(anyhow!("Inconsistent number of columns in line `{}`", line));
+        }
+        for (column, value) in columns.iter_mut().zip(values) {
+            column.push(value);
+        }
+    }
+    // Columns without a name are further ADC channels
+    for i in column_names.len()..columns.len() {
+        column_names.push(format!("ADC{}", i + 1 - DEFAULT_COLUMNS.len()));
+    }
+
+    let mut x = None;
+    let mut channels = Vec::new();
+    for (column, data) in column_names.iter().zip(columns) {
+        if column == "idx" {
+            continue;
+        }
+        let (name, unit, factor) = calibrate_column(column, &header)?;
+        let data: Vec<f64> = data.iter().map(|v| v * factor).collect();
+        if column == "V" && x.is_none() {
+            x = Some(data);
+        } else {
+            channels.push(SpectrumChannel { name, unit, data });
+        }
+    }
+    let x_data = x.ok_or_else(|| anyhow!("No bias column in {}", filename))?;
+
+    // Calibration of the xy piezo in Angstrom per DAC unit
+    let dac_to_nm = |gain: &str| {
+        header_value(&header, "Dacto[A]xy").unwrap_or_default()
+            * header_value(&header, gain).unwrap_or(1.0)
+            / 10.0
+    };
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+
+    let spectrum = SpmSpectrum {
+        spec_id: basename.to_string(),
+        x_label: "Bias".to_string(),
+        x_unit: "mV".to_string(),
+        x_data,
+        channels,
+        xpos: xpos_dac * dac_to_nm("GainX"),
+        ypos: ypos_dac * dac_to_nm("GainY"),
+    };
+
+    Ok(CreatecSpectrum {
+        filepath,
+        header,
+        num_points,
+        xpos_dac,
+        ypos_dac,
+        spectrum,
+    })
+}
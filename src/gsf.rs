@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::spm_image::SpmImage;
+use crate::utils::{length_to_nm, to_base_unit};
+
+const GSF_MAGIC: &str = "Gwyddion Simple Field 1.0\n";
+
+/// Gwyddion Simple Field (`.gsf`), a `Key = value` text header padded with NUL to a
+/// multiple of 4 bytes, followed by little-endian float32 data
+#[derive(Debug)]
+pub struct GsfImage {
+    pub filepath: PathBuf,
+    pub title: String,
+    /// Offset in nm
+    pub xoffset: f64,
+    /// Offset in nm
+    pub yoffset: f64,
+    /// All `Key = value` pairs of the header
+    pub header: BTreeMap<String, String>,
+    pub img_data: SpmImage,
+}
+
+pub fn read_gsf(filename: &str) -> Result<GsfImage> {
+    let bytes = fs::read(filename)?;
+    if !bytes.starts_with(GSF_MAGIC.as_bytes()) {
+        return Err(anyhow!("Not a Gwyddion Simple Field: {}", filename));
+    }
+    let header_len = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| anyhow!("No end of header in {}", filename))?;
+    // At least one NUL, up to the next multiple of 4
+    let data_start = (header_len / 4 + 1) * 4;
+
+    let header: BTreeMap<String, String> = String::from_utf8_lossy(&bytes[..header_len])
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let get = |key: &str| header.get(key).map(String::as_str);
+    let get_f64 = |key: &str| -> Result<Option<f64>> {
+        get(key)
+            .map(|value| value.parse::<f64>())
+            .transpose()
+            .map_err(|e| anyhow!("Invalid {}: {}", key, e))
+    };
+
+    let xres: usize = get("XRes")
+        .ok_or_else(|| anyhow!("Missing XRes"))?
+        .parse()?;
+    let yres: usize = get("YRes")
+        .ok_or_else(|| anyhow!("Missing YRes"))?
+        .parse()?;
+    let xy_unit = get("XYUnits").unwrap_or("m");
+    let xy_factor = length_to_nm(xy_unit).ok_or_else(|| anyhow!("Unknown XYUnits {}", xy_unit))?;
+
+    let num_pixels = xres * yres;
+    if bytes.len() < data_start + num_pixels * 4 {
+        return Err(anyhow!(
+            "Expected {}x{} pixels, file has {} bytes",
+            xres,
+            yres,
+            bytes.len()
+        ));
+    }
+    let img_data = bytes[data_start..data_start + num_pixels * 4]
+        .chunks_exact(4)
+        .map(|x| f64::from(f32::from_le_bytes([x[0], x[1], x[2], x[3]])))
+        .collect();
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+    let title = get("Title").unwrap_or(basename).to_string();
+
+    Ok(GsfImage {
+        title: title.clone(),
+        xoffset: get_f64("XOffset")?.unwrap_or_default() * xy_factor,
+        yoffset: get_f64("YOffset")?.unwrap_or_default() * xy_factor,
+        img_data: SpmImage {
+            img_id: title,
+            xres,
+            yres,
+            // XReal and YReal default to 1 according to the specification
+            xsize: get_f64("XReal")?.unwrap_or(1.0) * xy_factor,
+            ysize: get_f64("YReal")?.unwrap_or(1.0) * xy_factor,
+            z_unit: get("ZUnits").unwrap_or_default().to_string(),
+            img_data,
+        },
+        filepath,
+        header,
+    })
+}
+
+/// Writes the image with sizes in m and its values as float32 in the base unit of
+/// `z_unit`, as GSF requires SI units without prefix
+pub fn write_gsf(filename: &str, image: &SpmImage) -> Result<()> {
+    if image.img_data.len() != image.xres * image.yres {
+        return Err(anyhow!(
+            "Image {} has {} pixels, expected {}x{}",
+            image.img_id,
+            image.img_data.len(),
+            image.xres,
+            image.yres
+        ));
+    }
+
+    let mut header = GSF_MAGIC.to_string();
+    header.push_str(&format!("XRes = {}\n", image.xres));
+    header.push_str(&format!("YRes = {}\n", image.yres));
+    header.push_str(&format!("XReal = {:e}\n", image.xsize * 1e-9));
+    header.push_str(&format!("YReal = {:e}\n", image.ysize * 1e-9));
+    header.push_str("XYUnits = m\n");
+    let (z_unit, z_factor) = to_base_unit(&image.z_unit);
+    header.push_str(&format!("ZUnits = {}\n", z_unit));
+    header.push_str(&format!("Title = {}\n", image.img_id));
+
+    let mut buffer = header.into_bytes();
+    let padding = 4 - buffer.len() % 4;
+    buffer.resize(buffer.len() + padding, 0);
+    for x in image.img_data.iter() {
+        buffer.extend_from_slice(&((x * z_factor) as f32).to_le_bytes());
+    }
+    fs::write(filename, buffer)?;
+    Ok(())
+}
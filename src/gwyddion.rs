@@ -0,0 +1,592 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::mulfile::MulImage;
+use crate::spm_image::SpmImage;
+use crate::utils::{to_base_unit, Bytereading, Bytewriting};
+
+const GWY_MAGIC: &[u8] = b"GWYP";
+
+/// Serialized Gwyddion object, e.g. `GwyContainer` or `GwyDataField`, with its
+/// components in file order
+#[derive(Debug, Clone, PartialEq)]
+pub struct GwyObject {
+    pub name: String,
+    pub components: Vec<(String, GwyValue)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GwyValue {
+    Bool(bool),
+    Char(u8),
+    Int32(i32),
+    Int64(i64),
+    Double(f64),
+    String(String),
+    Object(GwyObject),
+    CharArray(Vec<u8>),
+    Int32Array(Vec<i32>),
+    Int64Array(Vec<i64>),
+    DoubleArray(Vec<f64>),
+    StringArray(Vec<String>),
+    ObjectArray(Vec<GwyObject>),
+}
+
+impl GwyObject {
+    pub fn get(&self, name: &str) -> Option<&GwyValue> {
+        self.components
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value)
+    }
+
+    fn get_i32(&self, name: &str) -> Option<i32> {
+        match self.get(name)? {
+            GwyValue::Int32(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    fn get_f64(&self, name: &str) -> Option<f64> {
+        match self.get(name)? {
+            GwyValue::Double(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    fn get_str(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            GwyValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn get_object(&self, name: &str) -> Option<&GwyObject> {
+        match self.get(name)? {
+            GwyValue::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+
+    fn get_doubles(&self, name: &str) -> Option<&[f64]> {
+        match self.get(name)? {
+            GwyValue::DoubleArray(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// Unit string of a `GwySIUnit` component, empty if missing
+    fn get_unit(&self, name: &str) -> String {
+        self.get_object(name)
+            .and_then(|unit| unit.get_str("unitstr"))
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+/// Mask of a channel, `true` for masked pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct GwyMask {
+    /// Number of the channel the mask belongs to
+    pub channel: usize,
+    pub xres: usize,
+    pub yres: usize,
+    pub data: Vec<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GwyGraph {
+    pub title: String,
+    pub x_label: String,
+    pub x_unit: String,
+    pub y_label: String,
+    pub y_unit: String,
+    pub curves: Vec<GwyCurve>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GwyCurve {
+    pub description: String,
+    pub x_data: Vec<f64>,
+    pub y_data: Vec<f64>,
+}
+
+/// Gwyddion native file (`.gwy`), a serialized `GwyContainer`
+#[derive(Debug)]
+pub struct GwyFile {
+    pub filepath: PathBuf,
+    /// The complete object tree
+    pub container: GwyObject,
+    /// Number of each channel, e.g. 0 for `/0/data`
+    pub channel_nums: Vec<usize>,
+    /// Data field of each channel, with its title as name
+    pub channels: Vec<SpmImage>,
+    pub masks: Vec<GwyMask>,
+    /// Metadata of each channel
+    pub metadata: BTreeMap<usize, BTreeMap<String, String>>,
+    pub graphs: Vec<GwyGraph>,
+}
+
+fn ensure(cursor: &Cursor<&[u8]>, num_bytes: usize) -> Result<()> {
+    let remaining = (cursor.get_ref().len() as u64).saturating_sub(cursor.position());
+    if (num_bytes as u64) > remaining {
+        return Err(anyhow!(
+            "Unexpected end of data at byte {}",
+            cursor.position()
+        ));
+    }
+    Ok(())
+}
+
+fn read_gwy_string(cursor: &mut Cursor<&[u8]>) -> Result<String> {
+    let start = cursor.position() as usize;
+    let len = cursor.get_ref()[start.min(cursor.get_ref().len())..]
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| anyhow!("Unterminated string at byte {}", start))?;
+    let s = String::from_utf8_lossy(&cursor.get_ref()[start..start + len]).to_string();
+    cursor.set_position((start + len + 1) as u64);
+    Ok(s)
+}
+
+fn read_array_len(cursor: &mut Cursor<&[u8]>, item_size: usize) -> Result<usize> {
+    ensure(cursor, 4)?;
+    let len = cursor.read_u32_le() as usize;
+    ensure(cursor, len * item_size)?;
+    Ok(len)
+}
+
+fn read_gwy_value(cursor: &mut Cursor<&[u8]>, type_: u8) -> Result<GwyValue> {
+    Ok(match type_ {
+        b'b' => {
+            ensure(cursor, 1)?;
+            GwyValue::Bool(cursor.read_u8_le() != 0)
+        }
+        b'c' => {
+            ensure(cursor, 1)?;
+            GwyValue::Char(cursor.read_u8_le())
+        }
+        b'i' => {
+            ensure(cursor, 4)?;
+            GwyValue::Int32(cursor.read_i32_le())
+        }
+        b'q' => {
+            ensure(cursor, 8)?;
+            GwyValue::Int64(cursor.read_u64_le() as i64)
+        }
+        b'd' => {
+            ensure(cursor, 8)?;
+            GwyValue::Double(cursor.read_f64_le())
+        }
+        b's' => GwyValue::String(read_gwy_string(cursor)?),
+        b'o' => GwyValue::Object(read_gwy_object(cursor)?),
+        b'C' => {
+            let len = read_array_len(cursor, 1)?;
+            GwyValue::CharArray((0..len).map(|_| cursor.read_u8_le()).collect())
+        }
+        b'I' => {
+            let len = read_array_len(cursor, 4)?;
+            GwyValue::Int32Array((0..len).map(|_| cursor.read_i32_le()).collect())
+        }
+        b'Q' => {
+            let len = read_array_len(cursor, 8)?;
+            GwyValue::Int64Array((0..len).map(|_| cursor.read_u64_le() as i64).collect())
+        }
+        b'D' => {
+            let len = read_array_len(cursor, 8)?;
+            GwyValue::DoubleArray((0..len).map(|_| cursor.read_f64_le()).collect())
+        }
+        b'S' => {
+            let len = read_array_len(cursor, 1)?;
+            GwyValue::StringArray(
+                (0..len)
+                    .map(|_| read_gwy_string(cursor))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        b'O' => {
+            let len = read_array_len(cursor, 1)?;
+            GwyValue::ObjectArray(
+                (0..len)
+                    .map(|_| read_gwy_object(cursor))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        other => {
+            return Err(anyhow!(
+                "Unknown component type `{}` at byte {}",
+                char::from(other),
+                cursor.position() - 1
+            ))
+        }
+    })
+}
+
+// Object name, size of the components in bytes, then the components as
+// name, type and value
+fn read_gwy_object(cursor: &mut Cursor<&[u8]>) -> Result<GwyObject> {
+    let name = read_gwy_string(cursor)?;
+    ensure(cursor, 4)?;
+    let size = cursor.read_u32_le() as usize;
+    ensure(cursor, size)?;
+
+    let start = cursor.position() as usize;
+    let mut components_cursor = Cursor::new(&cursor.get_ref()[start..start + size]);
+    let mut components = Vec::new();
+    while (components_cursor.position() as usize) < size {
+        let component_name = read_gwy_string(&mut components_cursor)?;
+        ensure(&components_cursor, 1)?;
+        let type_ = components_cursor.read_u8_le();
+        let value = read_gwy_value(&mut components_cursor, type_)?;
+        components.push((component_name, value));
+    }
+    cursor.set_position((start + size) as u64);
+
+    Ok(GwyObject { name, components })
+}
+
+/// Lateral sizes are stored in base units, e.g. m
+fn to_nm(value: f64, unit: &str) -> f64 {
+    match unit {
+        "m" => value * 1e9,
+        _ => value,
+    }
+}
+
+fn data_field_to_image(field: &GwyObject, img_id: String) -> Result<SpmImage> {
+    let xres = field.get_i32("xres").unwrap_or_default().max(0) as usize;
+    let yres = field.get_i32("yres").unwrap_or_default().max(0) as usize;
+    let unit_xy = field.get_unit("si_unit_xy");
+    let data = field
+        .get_doubles("data")
+        .ok_or_else(|| anyhow!("Data field {} without data", img_id))?;
+    if data.len() != xres * yres {
+        return Err(anyhow!(
+            "Data field {} has {} values, expected {}x{}",
+            img_id,
+            data.len(),
+            xres,
+            yres
+        ));
+    }
+    Ok(SpmImage {
+        img_id,
+        xres,
+        yres,
+        xsize: to_nm(field.get_f64("xreal").unwrap_or(1.0), &unit_xy),
+        ysize: to_nm(field.get_f64("yreal").unwrap_or(1.0), &unit_xy),
+        z_unit: field.get_unit("si_unit_z"),
+        img_data: data.to_vec(),
+    })
+}
+
+fn graph_model_to_graph(graph: &GwyObject) -> GwyGraph {
+    let curves = match graph.get("curves") {
+        Some(GwyValue::ObjectArray(curves)) => curves
+            .iter()
+            .map(|curve| GwyCurve {
+                description: curve.get_str("description").unwrap_or_default().to_string(),
+                x_data: curve.get_doubles("xdata").unwrap_or_default().to_vec(),
+                y_data: curve.get_doubles("ydata").unwrap_or_default().to_vec(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    GwyGraph {
+        title: graph.get_str("title").unwrap_or_default().to_string(),
+        x_label: graph
+            .get_str("bottom_label")
+            .unwrap_or_default()
+            .to_string(),
+        x_unit: graph.get_unit("x_unit"),
+        y_label: graph.get_str("left_label").unwrap_or_default().to_string(),
+        y_unit: graph.get_unit("y_unit"),
+        curves,
+    }
+}
+
+/// Number of the channel of a container key like `/0/data` or `/0/mask`
+fn channel_num(key: &str, suffix: &str) -> Option<usize> {
+    key.strip_prefix('/')?.strip_suffix(suffix)?.parse().ok()
+}
+
+pub fn read_gwy(filename: &str) -> Result<GwyFile> {
+    let bytes = fs::read(filename)?;
+    if !bytes.starts_with(GWY_MAGIC) {
+        return Err(anyhow!("Not a Gwyddion file: {}", filename));
+    }
+    let mut cursor = Cursor::new(&bytes[GWY_MAGIC.len()..]);
+    let container = read_gwy_object(&mut cursor)?;
+    if container.name != "GwyContainer" {
+        return Err(anyhow!("Expected GwyContainer, got {}", container.name));
+    }
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+
+    let mut channels = Vec::new();
+    let mut masks = Vec::new();
+    let mut metadata = BTreeMap::new();
+    let mut graphs = Vec::new();
+
+    for (key, value) in container.components.iter() {
+        let object = match value {
+            GwyValue::Object(object) => object,
+            _ => continue,
+        };
+        if let Some(num) = channel_num(key, "/data") {
+            let title = container
+                .get_str(&format!("/{}/data/title", num))
+                .map(str::to_string)
+                .unwrap_or_else(|| num.to_string());
+            let img_id = format!("{}_{}", basename, title);
+            channels.push((num, data_field_to_image(object, img_id)?));
+        } else if let Some(num) = channel_num(key, "/mask") {
+            let mask = data_field_to_image(object, String::new())?;
+            masks.push(GwyMask {
+                channel: num,
+                xres: mask.xres,
+                yres: mask.yres,
+                data: mask.img_data.iter().map(|&x| x > 0.0).collect(),
+            });
+        } else if let Some(num) = channel_num(key, "/meta") {
+            let meta = object
+                .components
+                .iter()
+                .filter_map(|(name, value)| match value {
+                    GwyValue::String(s) => Some((name.clone(), s.clone())),
+                    _ => None,
+                })
+                .collect();
+            metadata.insert(num, meta);
+        } else if object.name == "GwyGraphModel" {
+            graphs.push(graph_model_to_graph(object));
+        }
+    }
+
+    // Components are not stored in any particular order
+    channels.sort_by_key(|(num, _)| *num);
+    masks.sort_by_key(|mask| mask.channel);
+    let (channel_nums, channels) = channels.into_iter().unzip();
+
+    Ok(GwyFile {
+        filepath,
+        container,
+        channel_nums,
+        channels,
+        masks,
+        metadata,
+        graphs,
+    })
+}
+
+/// Image to write as a channel of a `.gwy` file
+#[derive(Debug)]
+pub struct GwyChannel<'a> {
+    pub image: &'a SpmImage,
+    pub title: String,
+    /// Offset in nm
+    pub xoffset: f64,
+    /// Offset in nm
+    pub yoffset: f64,
+    /// Shown in Gwyddion's metadata browser
+    pub metadata: BTreeMap<String, String>,
+    /// `true` for masked pixels, same size as the image
+    pub mask: Option<Vec<bool>>,
+}
+
+impl<'a> From<&'a MulImage> for GwyChannel<'a> {
+    fn from(img: &'a MulImage) -> Self {
+        let metadata = BTreeMap::from([
+            ("Bias".to_string(), format!("{} mV", img.bias)),
+            ("Current".to_string(), format!("{} nA", img.current)),
+            ("Date".to_string(), img.datetime.to_string()),
+            (
+                "Source file".to_string(),
+                img.filepath.display().to_string(),
+            ),
+        ]);
+        GwyChannel {
+            image: &img.img_data,
+            title: img.img_id.clone(),
+            xoffset: img.xoffset,
+            yoffset: img.yoffset,
+            metadata,
+            mask: None,
+        }
+    }
+}
+
+impl GwyObject {
+    fn new(name: &str) -> Self {
+        GwyObject {
+            name: name.to_string(),
+            components: Vec::new(),
+        }
+    }
+
+    fn with(mut self, name: &str, value: GwyValue) -> Self {
+        self.components.push((name.to_string(), value));
+        self
+    }
+}
+
+fn write_gwy_string(buffer: &mut Vec<u8>, s: &str) {
+    buffer.extend_from_slice(s.as_bytes());
+    buffer.push(0);
+}
+
+fn write_gwy_array_len(buffer: &mut Vec<u8>, len: usize) {
+    buffer.write_u32_le(len as u32);
+}
+
+fn write_gwy_value(buffer: &mut Vec<u8>, value: &GwyValue) {
+    match value {
+        GwyValue::Bool(b) => buffer.push(u8::from(*b)),
+        GwyValue::Char(c) => buffer.push(*c),
+        GwyValue::Int32(i) => buffer.write_i32_le(*i),
+        GwyValue::Int64(q) => buffer.extend_from_slice(&q.to_le_bytes()),
+        GwyValue::Double(d) => buffer.write_f64_le(*d),
+        GwyValue::String(s) => write_gwy_string(buffer, s),
+        GwyValue::Object(o) => write_gwy_object(buffer, o),
+        GwyValue::CharArray(a) => {
+            write_gwy_array_len(buffer, a.len());
+            buffer.extend_from_slice(a);
+        }
+        GwyValue::Int32Array(a) => {
+            write_gwy_array_len(buffer, a.len());
+            a.iter().for_each(|i| buffer.write_i32_le(*i));
+        }
+        GwyValue::Int64Array(a) => {
+            write_gwy_array_len(buffer, a.len());
+            a.iter()
+                .for_each(|q| buffer.extend_from_slice(&q.to_le_bytes()));
+        }
+        GwyValue::DoubleArray(a) => {
+            write_gwy_array_len(buffer, a.len());
+            a.iter().for_each(|d| buffer.write_f64_le(*d));
+        }
+        GwyValue::StringArray(a) => {
+            write_gwy_array_len(buffer, a.len());
+            a.iter().for_each(|s| write_gwy_string(buffer, s));
+        }
+        GwyValue::ObjectArray(a) => {
+            write_gwy_array_len(buffer, a.len());
+            a.iter().for_each(|o| write_gwy_object(buffer, o));
+        }
+    }
+}
+
+fn type_char(value: &GwyValue) -> u8 {
+    match value {
+        GwyValue::Bool(_) => b'b',
+        GwyValue::Char(_) => b'c',
+        GwyValue::Int32(_) => b'i',
+        GwyValue::Int64(_) => b'q',
+        GwyValue::Double(_) => b'd',
+        GwyValue::String(_) => b's',
+        GwyValue::Object(_) => b'o',
+        GwyValue::CharArray(_) => b'C',
+        GwyValue::Int32Array(_) => b'I',
+        GwyValue::Int64Array(_) => b'Q',
+        GwyValue::DoubleArray(_) => b'D',
+        GwyValue::StringArray(_) => b'S',
+        GwyValue::ObjectArray(_) => b'O',
+    }
+}
+
+fn write_gwy_object(buffer: &mut Vec<u8>, object: &GwyObject) {
+    let mut components = Vec::new();
+    for (name, value) in object.components.iter() {
+        write_gwy_string(&mut components, name);
+        components.push(type_char(value));
+        write_gwy_value(&mut components, value);
+    }
+    write_gwy_string(buffer, &object.name);
+    buffer.write_u32_le(components.len() as u32);
+    buffer.extend_from_slice(&components);
+}
+
+fn si_unit(unit: &str) -> GwyValue {
+    GwyValue::Object(
+        GwyObject::new("GwySIUnit").with("unitstr", GwyValue::String(unit.to_string())),
+    )
+}
+
+fn data_field(channel: &GwyChannel, data: Vec<f64>, z_unit: &str) -> GwyValue {
+    let img = channel.image;
+    GwyValue::Object(
+        GwyObject::new("GwyDataField")
+            .with("xres", GwyValue::Int32(img.xres as i32))
+            .with("yres", GwyValue::Int32(img.yres as i32))
+            .with("xreal", GwyValue::Double(img.xsize * 1e-9))
+            .with("yreal", GwyValue::Double(img.ysize * 1e-9))
+            .with("xoff", GwyValue::Double(channel.xoffset * 1e-9))
+            .with("yoff", GwyValue::Double(channel.yoffset * 1e-9))
+            .with("si_unit_xy", si_unit("m"))
+            .with("si_unit_z", si_unit(z_unit))
+            .with("data", GwyValue::DoubleArray(data)),
+    )
+}
+
+/// Writes the images as channels `/0/data`, `/1/data`, ... of a Gwyddion file
+pub fn write_gwy(filename: &str, channels: &[GwyChannel]) -> Result<()> {
+    let mut container = GwyObject::new("GwyContainer");
+
+    for (num, channel) in channels.iter().enumerate() {
+        let img = channel.image;
+        if img.img_data.len() != img.xres * img.yres {
+            return Err(anyhow!(
+                "Image {} has {} pixels, expected {}x{}",
+                img.img_id,
+                img.img_data.len(),
+                img.xres,
+                img.yres
+            ));
+        }
+        // Gwyddion drops the prefix of unit strings, so values are stored in base units
+        let (z_unit, factor) = to_base_unit(&img.z_unit);
+        let data = img.img_data.iter().map(|x| x * factor).collect();
+        container = container
+            .with(
+                &format!("/{}/data", num),
+                data_field(channel, data, &z_unit),
+            )
+            .with(
+                &format!("/{}/data/title", num),
+                GwyValue::String(channel.title.clone()),
+            );
+
+        if let Some(mask) = &channel.mask {
+            if mask.len() != img.img_data.len() {
+                return Err(anyhow!(
+                    "Mask of {} has {} pixels, expected {}",
+                    img.img_id,
+                    mask.len(),
+                    img.img_data.len()
+                ));
+            }
+            let data = mask.iter().map(|&m| f64::from(u8::from(m))).collect();
+            container = container.with(&format!("/{}/mask", num), data_field(channel, data, ""));
+        }
+
+        if !channel.metadata.is_empty() {
+            let meta = channel
+                .metadata
+                .iter()
+                .fold(GwyObject::new("GwyContainer"), |meta, (key, value)| {
+                    meta.with(key, GwyValue::String(value.clone()))
+                });
+            container = container.with(&format!("/{}/meta", num), GwyValue::Object(meta));
+        }
+    }
+
+    let mut buffer = GWY_MAGIC.to_vec();
+    write_gwy_object(&mut buffer, &container);
+    fs::write(filename, buffer)?;
+    Ok(())
+}
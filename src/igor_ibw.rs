@@ -1,4 +1,6 @@
 use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use ndarray::{ArrayD, IxDyn, ShapeBuilder};
 use std::{
     fs::read,
     io::{Cursor, Read},
@@ -8,14 +10,15 @@ use crate::utils::Bytereading;
 
 #[derive(Debug)]
 pub struct Ibw {
-    // creation_date:
-    // mod_date:
+    pub creation_date: DateTime<Utc>,
+    pub mod_date: DateTime<Utc>,
     pub npnts: i32,
     pub bname: String,
     pub n_dim: [i32; 4],
     pub x_step: [f64; 4],
     pub x_start: [f64; 4],
     pub data_units: String,
+    pub dim_units: [String; 4],
     pub data: NumericData,
     pub note: String,
     pub extended_data_units: Option<String>,
@@ -23,6 +26,72 @@ pub struct Ibw {
     pub dim_labels: Option<Vec<String>>,
 }
 
+#[derive(Debug)]
+pub struct IbwAxis {
+    /// Extended dimension units if set, otherwise the short dimension units
+    pub units: String,
+    /// Coordinate of each point along the axis: `x_start + i * x_step`
+    pub values: Vec<f64>,
+}
+
+impl Ibw {
+    /// Number of points along each used dimension
+    pub fn shape(&self) -> Vec<usize> {
+        self.n_dim
+            .iter()
+            .take_while(|&&n| n > 0)
+            .map(|&n| n as usize)
+            .collect()
+    }
+
+    /// Wave data reshaped to its dimensions, indexed as `[row, column, layer, chunk]`
+    /// like in Igor, which stores waves in column-major order
+    pub fn data_array(&self) -> Result<ArrayD<f64>> {
+        let shape = self.shape();
+        Ok(ArrayD::from_shape_vec(
+            IxDyn(&shape).f(),
+            self.data.to_f64(),
+        )?)
+    }
+
+    /// Labels of the single points along dimension `dim`, e.g. the channel names of
+    /// the layers of an image stack. Igor stores the label of the dimension itself
+    /// first, followed by one label per point, each padded to 32 bytes.
+    pub fn index_labels(&self, dim: usize) -> Vec<String> {
+        match &self.dim_labels {
+            Some(labels) if !labels[dim].is_empty() => labels[dim]
+                .as_bytes()
+                .chunks(32)
+                .skip(1)
+                .map(|label| {
+                    String::from_utf8_lossy(label)
+                        .trim_matches(char::from(0))
+                        .to_string()
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Scaled coordinates and units for each used dimension
+    pub fn axes(&self) -> Vec<IbwAxis> {
+        self.shape()
+            .iter()
+            .enumerate()
+            .map(|(dim, &n)| {
+                let units = match &self.dim_e_units {
+                    Some(e_units) if !e_units[dim].is_empty() => e_units[dim].clone(),
+                    _ => self.dim_units[dim].clone(),
+                };
+                let values = (0..n)
+                    .map(|i| self.x_start[dim] + i as f64 * self.x_step[dim])
+                    .collect();
+                IbwAxis { units, values }
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub enum BinHeader {
     V1(BinHeader1),
@@ -176,23 +245,131 @@ pub enum NumericData {
     Float64(Vec<f64>),
 }
 
+impl NumericData {
+    pub fn to_f64(&self) -> Vec<f64> {
+        match self {
+            NumericData::Int8(v) => v.iter().map(|&x| f64::from(x)).collect(),
+            NumericData::Int16(v) => v.iter().map(|&x| f64::from(x)).collect(),
+            NumericData::Int32(v) => v.iter().map(|&x| f64::from(x)).collect(),
+            NumericData::Uint8(v) => v.iter().map(|&x| f64::from(x)).collect(),
+            NumericData::Uint16(v) => v.iter().map(|&x| f64::from(x)).collect(),
+            NumericData::Uint32(v) => v.iter().map(|&x| f64::from(x)).collect(),
+            NumericData::Float32(v) => v.iter().map(|&x| f64::from(x)).collect(),
+            NumericData::Float64(v) => v.clone(),
+        }
+    }
+}
+
+/// Seconds between the Mac epoch (1904-01-01) used by Igor and the Unix epoch
+const MAC_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// Igor writes the local time of the acquiring machine, which is kept as is
+fn mac_seconds_to_datetime(seconds: u32) -> DateTime<Utc> {
+    Utc.timestamp_opt(i64::from(seconds) - MAC_EPOCH_OFFSET, 0)
+        .unwrap()
+}
+
+/// Waves that can not be read
+#[derive(Debug, Clone, PartialEq)]
+pub enum IbwError {
+    /// Only versions 2 and 5 are supported
+    UnsupportedVersion(i16),
+    /// Text and complex waves
+    UnsupportedType { bname: String, type_: i16 },
+    /// The data ends before a section of the wave
+    Truncated {
+        section: &'static str,
+        offset: u64,
+        num_bytes: i64,
+    },
+}
+
+impl std::fmt::Display for IbwError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IbwError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported wave version {}", version)
+            }
+            IbwError::UnsupportedType { bname, type_ } => {
+                write!(f, "Wave {} has unsupported type {:#x}", bname, type_)
+            }
+            IbwError::Truncated {
+                section,
+                offset,
+                num_bytes,
+            } => write!(
+                f,
+                "Wave is truncated, {} of {} bytes at byte {}",
+                section, num_bytes, offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IbwError {}
+
+const BIN_HEADER_2_SIZE: i64 = 16;
+const WAVE_HEADER_2_SIZE: i64 = 110;
+const BIN_HEADER_5_SIZE: i64 = 64;
+const WAVE_HEADER_5_SIZE: i64 = 320;
+
+/// Reading past the end would panic, negative sizes are from broken files
+fn check_remaining(
+    cursor: &Cursor<&[u8]>,
+    num_bytes: i64,
+    section: &'static str,
+) -> Result<(), IbwError> {
+    let remaining = cursor.get_ref().len() as i64 - cursor.position() as i64;
+    if num_bytes < 0 || num_bytes > remaining {
+        return Err(IbwError::Truncated {
+            section,
+            offset: cursor.position(),
+            num_bytes,
+        });
+    }
+    Ok(())
+}
+
+/// Size in bytes of a point of the supported numeric types
+fn point_size(data_type: i16) -> Option<i64> {
+    match data_type {
+        8 | 0x48 => Some(1),
+        0x10 | 0x50 => Some(2),
+        2 | 0x20 | 0x60 => Some(4),
+        4 => Some(8),
+        _ => None,
+    }
+}
+
 pub fn read_ibw(filename: &str) -> Result<Ibw> {
     let bytes = read(filename)?;
-    // let file_len = bytes.len();
-    let mut cursor = Cursor::new(bytes.as_slice());
+    parse_ibw(&bytes)
+}
+
+/// Parses a single wave, either from an `.ibw` file or from a wave record of a
+/// packed experiment
+pub(crate) fn parse_ibw(bytes: &[u8]) -> Result<Ibw> {
+    let mut cursor = Cursor::new(bytes);
+    check_remaining(&cursor, 2, "version")?;
     let version = cursor.read_i16_le();
     cursor.set_position(0);
 
-    let bin_header = match version {
-        2 => read_bin_header_2(&mut cursor),
-        5 => read_bin_header_5(&mut cursor),
-        _ => unreachable!("Not a version 2 or version 5 bin header"),
-    };
-
-    let wave_header = match version {
-        2 => read_wave_header_2(&mut cursor),
-        5 => read_wave_header_5(&mut cursor),
-        _ => unreachable!("Not a version 2 or version 5 wave header"),
+    let (bin_header, wave_header) = match version {
+        2 => {
+            check_remaining(&cursor, BIN_HEADER_2_SIZE + WAVE_HEADER_2_SIZE, "header")?;
+            (
+                read_bin_header_2(&mut cursor),
+                read_wave_header_2(&mut cursor),
+            )
+        }
+        5 => {
+            check_remaining(&cursor, BIN_HEADER_5_SIZE + WAVE_HEADER_5_SIZE, "header")?;
+            (
+                read_bin_header_5(&mut cursor),
+                read_wave_header_5(&mut cursor),
+            )
+        }
+        _ => return Err(IbwError::UnsupportedVersion(version).into()),
     };
 
     let npnts = match &wave_header {
@@ -205,29 +382,42 @@ pub fn read_ibw(filename: &str) -> Result<Ibw> {
         WaveHeader::V5(wh) => wh.type_,
     };
 
-    // TODO reshape data maybe
-    let data = read_numeric_data(&mut cursor, type_, npnts);
+    let bname = match &wave_header {
+        WaveHeader::V2(wh) => wh.bname.trim_matches(char::from(0)).to_string(),
+        WaveHeader::V5(wh) => wh.bname.trim_matches(char::from(0)).to_string(),
+    };
+
+    let unsupported = || IbwError::UnsupportedType {
+        bname: bname.clone(),
+        type_,
+    };
+    let point_size = point_size(type_).ok_or_else(unsupported)?;
+    check_remaining(&cursor, i64::from(npnts) * point_size, "data")?;
+    let data = read_numeric_data(&mut cursor, type_, npnts).ok_or_else(unsupported)?;
 
     // version 1,2,3 have 16 bytes of padding after numeric wave data
     if version == 1 || version == 2 || version == 3 {
+        check_remaining(&cursor, 16, "padding")?;
         let pos = cursor.position();
         cursor.set_position(pos + 16);
     }
 
+    // The dependency formula is not used
+    if let BinHeader::V5(bh) = &bin_header {
+        check_remaining(&cursor, i64::from(bh.formula_size), "formula")?;
+        cursor.skip(bh.formula_size as u64);
+    }
+
     // Optional Data
     // v1: no optional data
     // v2: wave note data
     // v3: wave note data, wave dependency formula
     // v5: wave dependency formula, wave note data, extended data units data, extended dimension units data, dimension label data, String indices used for text waves only
 
-    let note = read_note(&mut cursor, &bin_header);
-    let extended_data_units = read_extended_data_units(&mut cursor, &bin_header);
-    let dim_e_units = read_dim_e_units(&mut cursor, &bin_header);
-    let dim_labels = read_dim_labels(&mut cursor, &bin_header);
-    let bname = match &wave_header {
-        WaveHeader::V2(wh) => wh.bname.trim_matches(char::from(0)).to_string(),
-        WaveHeader::V5(wh) => wh.bname.trim_matches(char::from(0)).to_string(),
-    };
+    let note = read_note(&mut cursor, &bin_header)?;
+    let extended_data_units = read_extended_data_units(&mut cursor, &bin_header)?;
+    let dim_e_units = read_dim_e_units(&mut cursor, &bin_header)?;
+    let dim_labels = read_dim_labels(&mut cursor, &bin_header)?;
     let n_dim = match &wave_header {
         WaveHeader::V2(wh) => [wh.npnts, 0, 0, 0],
         WaveHeader::V5(wh) => wh.n_dim,
@@ -240,18 +430,42 @@ pub fn read_ibw(filename: &str) -> Result<Ibw> {
         WaveHeader::V2(wh) => [wh.hs_b, 0_f64, 0_f64, 0_f64],
         WaveHeader::V5(wh) => wh.sf_b,
     };
+    let creation_date = match &wave_header {
+        WaveHeader::V2(wh) => mac_seconds_to_datetime(wh.creation_date),
+        WaveHeader::V5(wh) => mac_seconds_to_datetime(wh.creation_date),
+    };
+    let mod_date = match &wave_header {
+        WaveHeader::V2(wh) => mac_seconds_to_datetime(wh.mod_date),
+        WaveHeader::V5(wh) => mac_seconds_to_datetime(wh.mod_date),
+    };
     let data_units = match &wave_header {
         WaveHeader::V2(wh) => wh.data_units.trim_matches(char::from(0)).to_string(),
         WaveHeader::V5(wh) => wh.data_units.trim_matches(char::from(0)).to_string(),
     };
+    let dim_units = match &wave_header {
+        WaveHeader::V2(wh) => [
+            wh.x_units.trim_matches(char::from(0)).to_string(),
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+        ],
+        WaveHeader::V5(wh) => wh.dim_units.map(|u| {
+            String::from_utf8_lossy(&u)
+                .trim_matches(char::from(0))
+                .to_string()
+        }),
+    };
 
     Ok(Ibw {
+        creation_date,
+        mod_date,
         npnts,
         bname,
         n_dim,
         x_step,
         x_start,
         data_units,
+        dim_units,
         data,
         note,
         extended_data_units,
@@ -260,67 +474,83 @@ pub fn read_ibw(filename: &str) -> Result<Ibw> {
     })
 }
 
-fn read_note(cursor: &mut Cursor<&[u8]>, bin_header: &BinHeader) -> String {
+fn read_note(cursor: &mut Cursor<&[u8]>, bin_header: &BinHeader) -> Result<String, IbwError> {
     let note_size = match bin_header {
         BinHeader::V2(bh) => bh.note_size,
         BinHeader::V5(bh) => bh.note_size,
         _ => unreachable!("Only version 2 and version 5 bin headers implemented"),
     };
 
+    check_remaining(cursor, i64::from(note_size), "note")?;
     if note_size != 0 {
-        cursor.read_string(note_size as usize).replace("\r", "\n")
+        Ok(cursor.read_string(note_size as usize).replace("\r", "\n"))
     } else {
-        "".to_string()
+        Ok("".to_string())
     }
 }
 
-fn read_extended_data_units(cursor: &mut Cursor<&[u8]>, bin_header: &BinHeader) -> Option<String> {
+fn read_extended_data_units(
+    cursor: &mut Cursor<&[u8]>,
+    bin_header: &BinHeader,
+) -> Result<Option<String>, IbwError> {
     // extended data units
     match bin_header {
         BinHeader::V5(bh) => {
+            check_remaining(cursor, i64::from(bh.data_e_units_size), "data units")?;
             if bh.data_e_units_size != 0 {
-                Some(cursor.read_string(bh.data_e_units_size as usize))
+                Ok(Some(cursor.read_string(bh.data_e_units_size as usize)))
             } else {
-                None
+                Ok(None)
             }
         }
-        _ => None,
+        _ => Ok(None),
     }
 }
 
-fn read_dim_e_units(cursor: &mut Cursor<&[u8]>, bin_header: &BinHeader) -> Option<Vec<String>> {
+// Strings of the four dimensions, one after the other
+fn read_dim_strings(
+    cursor: &mut Cursor<&[u8]>,
+    sizes: &[i32; 4],
+    section: &'static str,
+) -> Result<Vec<String>, IbwError> {
+    sizes
+        .iter()
+        .map(|i| {
+            check_remaining(cursor, i64::from(*i), section)?;
+            if *i != 0 {
+                Ok(cursor.read_string(*i as usize))
+            } else {
+                Ok("".to_string())
+            }
+        })
+        .collect()
+}
+
+fn read_dim_e_units(
+    cursor: &mut Cursor<&[u8]>,
+    bin_header: &BinHeader,
+) -> Result<Option<Vec<String>>, IbwError> {
     match bin_header {
-        BinHeader::V5(bh) => Some(
-            bh.dim_e_units_size
-                .iter()
-                .map(|i| {
-                    if *i != 0 {
-                        cursor.read_string(*i as usize)
-                    } else {
-                        "".to_string()
-                    }
-                })
-                .collect::<Vec<String>>(),
-        ),
-        _ => None,
+        BinHeader::V5(bh) => Ok(Some(read_dim_strings(
+            cursor,
+            &bh.dim_e_units_size,
+            "dimension units",
+        )?)),
+        _ => Ok(None),
     }
 }
 
-fn read_dim_labels(cursor: &mut Cursor<&[u8]>, bin_header: &BinHeader) -> Option<Vec<String>> {
+fn read_dim_labels(
+    cursor: &mut Cursor<&[u8]>,
+    bin_header: &BinHeader,
+) -> Result<Option<Vec<String>>, IbwError> {
     match bin_header {
-        BinHeader::V5(bh) => Some(
-            bh.dim_labels_size
-                .iter()
-                .map(|i| {
-                    if *i != 0 {
-                        cursor.read_string(*i as usize)
-                    } else {
-                        "".to_string()
-                    }
-                })
-                .collect::<Vec<String>>(),
-        ),
-        _ => None,
+        BinHeader::V5(bh) => Ok(Some(read_dim_strings(
+            cursor,
+            &bh.dim_labels_size,
+            "dimension labels",
+        )?)),
+        _ => Ok(None),
     }
 }
 
@@ -554,78 +784,65 @@ fn read_numeric_data(
     cursor: &mut Cursor<&[u8]>,
     data_type: i16,
     num_data_points: i32,
-) -> NumericData {
+) -> Option<NumericData> {
     match data_type {
-        0 => todo!("Text Waves"),
-        1 => todo!("Complex"),
         2 => {
             let mut v = Vec::with_capacity((num_data_points / 4) as usize);
             for _ in 0..num_data_points {
                 v.push(cursor.read_f32_le());
             }
-            NumericData::Float32(v)
+            Some(NumericData::Float32(v))
         }
-        3 => todo!("Complex 64"),
         4 => {
             let mut v = Vec::with_capacity((num_data_points / 8) as usize);
             for _ in 0..num_data_points {
                 v.push(cursor.read_f64_le());
             }
-            NumericData::Float64(v)
+            Some(NumericData::Float64(v))
         }
-        5 => todo!("Complex 128"),
         8 => {
             let mut v = Vec::with_capacity((num_data_points) as usize);
             for _ in 0..num_data_points {
                 v.push(cursor.read_i8_le());
             }
-            NumericData::Int8(v)
+            Some(NumericData::Int8(v))
         }
-        9 => todo!("Complex Int8"),
         0x10 => {
             let mut v = Vec::with_capacity((num_data_points / 2) as usize);
             for _ in 0..num_data_points {
                 v.push(cursor.read_i16_le());
             }
-            NumericData::Int16(v)
+            Some(NumericData::Int16(v))
         }
-        0x11 => todo!("Complex Int16"),
-
         0x20 => {
             let mut v = Vec::with_capacity((num_data_points / 4) as usize);
             for _ in 0..num_data_points {
                 v.push(cursor.read_i32_le());
             }
-            NumericData::Int32(v)
+            Some(NumericData::Int32(v))
         }
-        0x21 => todo!("Complex Int32"),
-
         0x48 => {
             let mut v = Vec::with_capacity((num_data_points) as usize);
             for _ in 0..num_data_points {
                 v.push(cursor.read_u8_le());
             }
-            NumericData::Uint8(v)
+            Some(NumericData::Uint8(v))
         }
-        0x49 => todo!("Complex UInt8"),
-
         0x50 => {
             let mut v = Vec::with_capacity((num_data_points / 2) as usize);
             for _ in 0..num_data_points {
                 v.push(cursor.read_u16_le());
             }
-            NumericData::Uint16(v)
+            Some(NumericData::Uint16(v))
         }
-        0x51 => todo!("Complex UInt16 Data"),
-
         0x60 => {
             let mut v = Vec::with_capacity((num_data_points / 4) as usize);
             for _ in 0..num_data_points {
                 v.push(cursor.read_u32_le());
             }
-            NumericData::Uint32(v)
+            Some(NumericData::Uint32(v))
         }
-        0x61 => todo!("Complex UInt32 Data"),
-        _ => unreachable!(),
+        // Text and complex waves
+        _ => None,
     }
 }
@@ -0,0 +1,184 @@
+use anyhow::{anyhow, Result};
+use std::{fs::read, io::Cursor};
+
+use crate::igor_ibw::{parse_ibw, Ibw, IbwError};
+use crate::utils::Bytereading;
+
+/// Igor packed experiment (`.pxp`), consisting of a list of records
+#[derive(Debug)]
+pub struct PackedExperiment {
+    pub root: DataFolder,
+    /// Command history
+    pub history: String,
+    /// Contents of the procedure window
+    pub procedure: String,
+    /// Commands to recreate windows, graphs and tables
+    pub recreation: String,
+    /// Paths of text and complex waves, which are skipped, e.g. `root:spectra:labels`
+    pub unsupported_waves: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct DataFolder {
+    pub name: String,
+    pub waves: Vec<Ibw>,
+    pub folders: Vec<DataFolder>,
+}
+
+impl DataFolder {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            waves: Vec::new(),
+            folders: Vec::new(),
+        }
+    }
+
+    fn collect_waves<'a>(&'a self, parent: &str, waves: &mut Vec<(String, &'a Ibw)>) {
+        let path = format!("{}{}:", parent, self.name);
+        for wave in self.waves.iter() {
+            waves.push((path.clone(), wave));
+        }
+        for folder in self.folders.iter() {
+            folder.collect_waves(&path, waves);
+        }
+    }
+}
+
+impl PackedExperiment {
+    /// All waves of the experiment with their data folder path, e.g. `root:spectra:`
+    pub fn waves(&self) -> Vec<(String, &Ibw)> {
+        let mut waves = Vec::new();
+        self.root.collect_waves("", &mut waves);
+        waves
+    }
+}
+
+#[derive(Debug)]
+enum PackedRecordType {
+    Unused,
+    Variables,
+    History,
+    Wave,
+    Recreation,
+    Procedure,
+    GetHistory,
+    PackedFile,
+    DataFolderStart,
+    DataFolderEnd,
+    Unknown,
+}
+
+impl PackedRecordType {
+    fn from_num(num: u16) -> Self {
+        match num {
+            0 => Self::Unused,
+            1 => Self::Variables,
+            2 => Self::History,
+            3 => Self::Wave,
+            4 => Self::Recreation,
+            5 => Self::Procedure,
+            6 => Self::Unused,
+            7 => Self::GetHistory,
+            8 => Self::PackedFile,
+            9 => Self::DataFolderStart,
+            10 => Self::DataFolderEnd,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Records of superceded data are kept in the file but flagged with the high bit
+const SUPERCEDED_MASK: u16 = 0x8000;
+
+// Maximum length of a data folder name plus the trailing null
+const MAX_DATA_FOLDER_NAME: usize = 32;
+
+pub fn read_pxp(filename: &str) -> Result<PackedExperiment> {
+    let bytes = read(filename)?;
+    let file_len = bytes.len() as u64;
+    let mut cursor = Cursor::new(bytes.as_slice());
+
+    // Stack of currently open data folders, the first one is always root
+    let mut folders = vec![DataFolder::new("root")];
+    let mut history = String::new();
+    let mut procedure = String::new();
+    let mut recreation = String::new();
+    let mut unsupported_waves = Vec::new();
+
+    while cursor.position() + 8 <= file_len {
+        let record_type = cursor.read_u16_le();
+        let _version = cursor.read_i16_le();
+        let num_data_bytes = cursor.read_i32_le();
+
+        let start = cursor.position() as usize;
+        let end = usize::try_from(num_data_bytes)
+            .map_err(|_| anyhow!("Record at byte {} has negative length", start))?
+            .checked_add(start)
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| anyhow!("Record at byte {} exceeds file length {}", start, file_len))?;
+        let data = &bytes[start..end];
+        cursor.set_position(end as u64);
+
+        if record_type & SUPERCEDED_MASK != 0 {
+            continue;
+        }
+
+        match PackedRecordType::from_num(record_type) {
+            PackedRecordType::Wave => match parse_ibw(data) {
+                Ok(wave) => folders.last_mut().unwrap().waves.push(wave),
+                Err(err) => match err.downcast_ref::<IbwError>() {
+                    Some(IbwError::UnsupportedType { bname, .. }) => {
+                        let path: Vec<&str> = folders.iter().map(|f| f.name.as_str()).collect();
+                        unsupported_waves.push(format!("{}:{}", path.join(":"), bname));
+                    }
+                    _ => return Err(err),
+                },
+            },
+            PackedRecordType::DataFolderStart => {
+                let name_len = data
+                    .iter()
+                    .position(|&b| b == 0)
+                    .unwrap_or(data.len())
+                    .min(MAX_DATA_FOLDER_NAME);
+                folders.push(DataFolder::new(&read_text(&data[..name_len])));
+            }
+            PackedRecordType::DataFolderEnd => {
+                if folders.len() < 2 {
+                    return Err(anyhow!("Data folder end without start at byte {}", start));
+                }
+                let folder = folders.pop().unwrap();
+                folders.last_mut().unwrap().folders.push(folder);
+            }
+            PackedRecordType::History => history = read_text(data),
+            PackedRecordType::Procedure => procedure = read_text(data),
+            PackedRecordType::Recreation => recreation = read_text(data),
+            PackedRecordType::Unused
+            | PackedRecordType::Variables
+            | PackedRecordType::GetHistory
+            | PackedRecordType::PackedFile
+            | PackedRecordType::Unknown => (),
+        }
+    }
+
+    // Close folders left open by a truncated file
+    while folders.len() > 1 {
+        let folder = folders.pop().unwrap();
+        folders.last_mut().unwrap().folders.push(folder);
+    }
+
+    Ok(PackedExperiment {
+        root: folders.pop().unwrap(),
+        history,
+        procedure,
+        recreation,
+        unsupported_waves,
+    })
+}
+
+// Text is stored with Mac line endings and in the platform's encoding
+fn read_text(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_end_matches(char::from(0))
+        .replace('\r', "\n")
+}
@@ -0,0 +1,240 @@
+use std::fs::File;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use tiff::decoder::{Decoder, DecodingResult, Limits};
+use tiff::tags::Tag;
+
+use crate::spm_image::SpmImage;
+
+/// JPK image (`.jpk`, `.jpk-qi-image`), a TIFF with a preview as first page and a page
+/// for each channel, scan parameters and calibrations are stored in private tags
+#[derive(Debug)]
+pub struct JpkImage {
+    pub filepath: PathBuf,
+    pub start_date: String,
+    /// Size in pixels
+    pub xres: usize,
+    /// Size in pixels
+    pub yres: usize,
+    /// Size in nm
+    pub xsize: f64,
+    /// Size in nm
+    pub ysize: f64,
+    /// Offset in nm
+    pub xoffset: f64,
+    /// Offset in nm
+    pub yoffset: f64,
+    pub channels: Vec<JpkChannel>,
+}
+
+#[derive(Debug)]
+pub struct JpkChannel {
+    /// E.g. `height`
+    pub name: String,
+    /// E.g. `Height`
+    pub fancy_name: String,
+    pub retrace: bool,
+    pub slots: Vec<JpkSlot>,
+    /// Slot in which `img_data` is calibrated
+    pub default_slot: usize,
+    pub img_data: SpmImage,
+}
+
+/// Calibration of the values in the slot `parent`, or of the raw values
+#[derive(Debug, Clone, PartialEq)]
+pub struct JpkSlot {
+    /// E.g. `nominal`
+    pub name: String,
+    pub parent: Option<String>,
+    pub unit: String,
+    /// `LinearScaling` or `NullScaling`
+    pub scaling_type: String,
+    pub multiply: f64,
+    pub offset: f64,
+}
+
+const TAG_START_DATE: u16 = 0x8003;
+const TAG_GRID_X0: u16 = 0x8040;
+const TAG_GRID_Y0: u16 = 0x8041;
+const TAG_GRID_U_LENGTH: u16 = 0x8042;
+const TAG_GRID_V_LENGTH: u16 = 0x8043;
+const TAG_GRID_I_LENGTH: u16 = 0x8046;
+const TAG_GRID_J_LENGTH: u16 = 0x8047;
+const TAG_CHANNEL: u16 = 0x8050;
+const TAG_CHANNEL_RETRACE: u16 = 0x8051;
+const TAG_CHANNEL_FANCY_NAME: u16 = 0x8052;
+const TAG_NUM_SLOTS: u16 = 0x8080;
+const TAG_DEFAULT_SLOT: u16 = 0x8081;
+// Tags of the first slot, the ones of slot `i` are at an offset of `i * SLOT_STRIDE`
+const TAG_SLOT_NAME: u16 = 0x8090;
+const TAG_SLOT_PARENT: u16 = 0x8092;
+const TAG_CALIBRATION_UNIT: u16 = 0x80A1;
+const TAG_SCALING_TYPE: u16 = 0x80A2;
+const TAG_SCALING_MULTIPLY: u16 = 0x80A3;
+const TAG_SCALING_OFFSET: u16 = 0x80A4;
+const SLOT_STRIDE: u16 = 0x30;
+// Slots whose tags fit below 0xFFFF
+const MAX_SLOTS: usize = ((u16::MAX - TAG_SCALING_OFFSET) / SLOT_STRIDE) as usize + 1;
+
+struct JpkTags<'a>(&'a mut Decoder<File>);
+
+impl JpkTags<'_> {
+    fn string(&mut self, tag: u16) -> Result<Option<String>> {
+        match self.0.find_tag(Tag::Unknown(tag))? {
+            Some(value) => Ok(Some(
+                value.into_string()?.trim_end_matches('\0').to_string(),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn f64(&mut self, tag: u16) -> Result<Option<f64>> {
+        match self.0.find_tag(Tag::Unknown(tag))? {
+            Some(value) => Ok(Some(value.into_f64()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn usize(&mut self, tag: u16) -> Result<Option<usize>> {
+        Ok(self
+            .0
+            .find_tag_unsigned::<u32>(Tag::Unknown(tag))?
+            .map(|v| v as usize))
+    }
+
+    /// Slot `index`, which must be below `MAX_SLOTS`
+    fn slot(&mut self, index: usize) -> Result<JpkSlot> {
+        let offset = index as u16 * SLOT_STRIDE;
+        Ok(JpkSlot {
+            name: self.string(TAG_SLOT_NAME + offset)?.unwrap_or_default(),
+            parent: self.string(TAG_SLOT_PARENT + offset)?,
+            unit: self
+                .string(TAG_CALIBRATION_UNIT + offset)?
+                .unwrap_or_default(),
+            scaling_type: self.string(TAG_SCALING_TYPE + offset)?.unwrap_or_default(),
+            multiply: self.f64(TAG_SCALING_MULTIPLY + offset)?.unwrap_or(1.0),
+            offset: self.f64(TAG_SCALING_OFFSET + offset)?.unwrap_or_default(),
+        })
+    }
+}
+
+/// Applies the calibrations from the raw values up to the given slot
+fn calibrate(raw: Vec<f64>, slots: &[JpkSlot], slot: &JpkSlot) -> Result<Vec<f64>> {
+    let mut chain = vec![slot];
+    while let Some(parent) = &chain.last().unwrap().parent {
+        let parent = slots
+            .iter()
+            .find(|s| &s.name == parent)
+            .ok_or_else(|| anyhow!("Unknown calibration slot {}", parent))?;
+        if chain.contains(&parent) {
+            return Err(anyhow!("Circular calibration of slot {}", slot.name));
+        }
+        chain.push(parent);
+    }
+    Ok(raw
+        .into_iter()
+        .map(|value| {
+            chain
+                .iter()
+                .rev()
+                .fold(value, |v, s| match s.scaling_type.as_str() {
+                    "LinearScaling" => v * s.multiply + s.offset,
+                    _ => v,
+                })
+        })
+        .collect())
+}
+
+fn decoding_result_to_f64(result: DecodingResult) -> Result<Vec<f64>> {
+    Ok(match result {
+        DecodingResult::U8(data) => data.into_iter().map(f64::from).collect(),
+        DecodingResult::U16(data) => data.into_iter().map(f64::from).collect(),
+        DecodingResult::U32(data) => data.into_iter().map(f64::from).collect(),
+        DecodingResult::I8(data) => data.into_iter().map(f64::from).collect(),
+        DecodingResult::I16(data) => data.into_iter().map(f64::from).collect(),
+        DecodingResult::I32(data) => data.into_iter().map(f64::from).collect(),
+        DecodingResult::F32(data) => data.into_iter().map(f64::from).collect(),
+        DecodingResult::F64(data) => data,
+        _ => return Err(anyhow!("Unsupported sample format")),
+    })
+}
+
+pub fn read_jpk_tiff(filename: &str) -> Result<JpkImage> {
+    let mut decoder = Decoder::new(File::open(filename)?)?.with_limits(Limits::unlimited());
+
+    // Scan parameters of the preview page, lengths in m
+    let mut tags = JpkTags(&mut decoder);
+    let u_length = tags
+        .f64(TAG_GRID_U_LENGTH)?
+        .ok_or_else(|| anyhow!("Not a JPK image: {}", filename))?;
+    let v_length = tags.f64(TAG_GRID_V_LENGTH)?.unwrap_or(u_length);
+    let xoffset = tags.f64(TAG_GRID_X0)?.unwrap_or_default() * 1e9;
+    let yoffset = tags.f64(TAG_GRID_Y0)?.unwrap_or_default() * 1e9;
+    let xres = tags.usize(TAG_GRID_I_LENGTH)?.unwrap_or_default();
+    let yres = tags.usize(TAG_GRID_J_LENGTH)?.unwrap_or_default();
+    let start_date = tags.string(TAG_START_DATE)?.unwrap_or_default();
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+
+    let mut channels = Vec::new();
+    while decoder.more_images() {
+        decoder.next_image()?;
+        let mut tags = JpkTags(&mut decoder);
+        let Some(name) = tags.string(TAG_CHANNEL)? else {
+            continue;
+        };
+        let fancy_name = tags.string(TAG_CHANNEL_FANCY_NAME)?.unwrap_or(name.clone());
+        let retrace = tags.usize(TAG_CHANNEL_RETRACE)?.unwrap_or_default() != 0;
+        let num_slots = tags.usize(TAG_NUM_SLOTS)?.unwrap_or_default();
+        if num_slots > MAX_SLOTS {
+            return Err(anyhow!(
+                "Invalid number of slots {} of channel {}",
+                num_slots,
+                name
+            ));
+        }
+        let default_slot = tags.usize(TAG_DEFAULT_SLOT)?.unwrap_or_default();
+        let slots = (0..num_slots)
+            .map(|i| tags.slot(i))
+            .collect::<Result<Vec<_>>>()?;
+
+        let (width, height) = decoder.dimensions()?;
+        let raw = decoding_result_to_f64(decoder.read_image()?)?;
+        let (img_data, z_unit) = match slots.get(default_slot) {
+            Some(slot) => (calibrate(raw, &slots, slot)?, slot.unit.clone()),
+            None => (raw, String::new()),
+        };
+
+        let direction = if retrace { "bwd" } else { "fwd" };
+        channels.push(JpkChannel {
+            img_data: SpmImage {
+                img_id: format!("{}_{}_{}", basename, name, direction),
+                xres: width as usize,
+                yres: height as usize,
+                xsize: u_length * 1e9,
+                ysize: v_length * 1e9,
+                z_unit,
+                img_data,
+            },
+            name,
+            fancy_name,
+            retrace,
+            slots,
+            default_slot,
+        });
+    }
+
+    Ok(JpkImage {
+        filepath,
+        start_date,
+        xres,
+        yres,
+        xsize: u_length * 1e9,
+        ysize: v_length * 1e9,
+        xoffset,
+        yoffset,
+        channels,
+    })
+}
@@ -1,7 +1,25 @@
+pub mod ascii_import;
+pub mod asylum_ibw;
+pub mod createc_dat;
+pub mod createc_vert;
+pub mod gsf;
+pub mod gwyddion;
 pub mod igor_ibw;
+pub mod igor_pxp;
+pub mod jpk_tiff;
 pub mod mulfile;
+pub mod nanonis_3ds;
+pub mod nanonis_dat;
+pub mod nanonis_sxm;
+pub mod nanoscope;
+pub mod ntmdt;
+pub mod numpy;
 // pub mod omicron_matrix;
+pub mod omicron_scala;
+pub mod park_tiff;
 // pub mod rhk_sm4;
 mod rocket;
 pub mod spm_image;
+pub mod spm_spectrum;
 mod utils;
+pub mod wsxm;
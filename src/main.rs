@@ -11,7 +11,7 @@ use clap::Parser;
 use notify::Watcher;
 // use spm_rs::igor_ibw::read_ibw;
 use spm_rs::{
-    mulfile::{read_mul, MulImage},
+    mulfile::{read_mul_partial, MulImage},
     spm_image::SpmImage,
 };
 
@@ -109,7 +109,10 @@ impl MyApp {
 
         match args.filename {
             Some(filename) if filename.ends_with(".mul") || filename.ends_with(".flm") => {
-                let mulfile = read_mul(&filename).unwrap();
+                let (mulfile, err) = read_mul_partial(&filename).unwrap();
+                if let Some(err) = err {
+                    eprintln!("{}: {}", filename, err);
+                }
 
                 let active_images = mulfile
                     .iter()
@@ -411,7 +414,11 @@ impl MyApp {
         if p.extension().is_some_and(|x| x == "mul") {
             let filename = p.file_name().unwrap();
 
-            let mulfile = read_mul(&p.to_string_lossy()).unwrap();
+            // The file may still be written, load the complete images
+            let (mulfile, err) = read_mul_partial(&p.to_string_lossy()).unwrap();
+            if let Some(err) = err {
+                eprintln!("{}: {}", p.display(), err);
+            }
 
             for mul_img in mulfile.iter() {
                 self.active_images
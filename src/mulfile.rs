@@ -1,15 +1,113 @@
 use std::fs;
 use std::io::{Cursor, Read, Seek, SeekFrom};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use chrono::prelude::*;
 use chrono::{DateTime, Utc};
 
 use crate::spm_image::flip_img_data;
 use crate::spm_image::SpmImage;
-use crate::utils::{read_i16_le_bytes, Bytereading};
+use crate::spm_spectrum::{linspace, SpectrumChannel, SpmSpectrum};
+use crate::utils::{read_i16_le_bytes, Bytereading, Bytewriting};
+
+const MUL_BLOCK: i32 = 128;
+
+/// Problems with the content of a MUL file, e.g. when it is still being written
+#[derive(Debug, Clone, PartialEq)]
+pub enum MulError {
+    /// Fewer than one block left for the header of the next image
+    TruncatedHeader {
+        block: i32,
+        num_bytes: usize,
+    },
+    /// The file ends before the last block of the image
+    TruncatedImage {
+        img_num: i16,
+        block: i32,
+        expected_blocks: i16,
+        available_blocks: i32,
+    },
+    InvalidSize {
+        img_num: i16,
+        block: i32,
+        size: i16,
+    },
+    InvalidResolution {
+        img_num: i16,
+        block: i32,
+        xres: i16,
+        yres: i16,
+    },
+    InvalidDatetime {
+        img_num: i16,
+        block: i32,
+    },
+    /// Pixels and point scans need more blocks than given in the header
+    DataExceedsSize {
+        img_num: i16,
+        block: i32,
+        size: i16,
+    },
+}
+
+impl std::fmt::Display for MulError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MulError::TruncatedHeader { block, num_bytes } => write!(
+                f,
+                "Truncated header at block {}, only {} bytes left",
+                block, num_bytes
+            ),
+            MulError::TruncatedImage {
+                img_num,
+                block,
+                expected_blocks,
+                available_blocks,
+            } => write!(
+                f,
+                "Image {} at block {} is truncated, {} of {} blocks available",
+                img_num, block, available_blocks, expected_blocks
+            ),
+            MulError::InvalidSize {
+                img_num,
+                block,
+                size,
+            } => write!(
+                f,
+                "Image {} at block {} has invalid size {}",
+                img_num, block, size
+            ),
+            MulError::InvalidResolution {
+                img_num,
+                block,
+                xres,
+                yres,
+            } => write!(
+                f,
+                "Image {} at block {} has invalid resolution {}x{}",
+                img_num, block, xres, yres
+            ),
+            MulError::InvalidDatetime { img_num, block } => write!(
+                f,
+                "Image {} at block {} has an invalid date",
+                img_num, block
+            ),
+            MulError::DataExceedsSize {
+                img_num,
+                block,
+                size,
+            } => write!(
+                f,
+                "Data of image {} at block {} exceeds its size of {} blocks",
+                img_num, block, size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MulError {}
 
 #[derive(Debug)]
 pub struct MulImage {
@@ -23,31 +121,75 @@ pub struct MulImage {
     pub yres: usize,
     pub zres: i16,
     pub datetime: DateTime<Utc>,
-    /// Size in physical units
-    pub xsize: i16,
-    /// Size in pixels
-    pub ysize: i16,
-    pub xoffset: i16,
-    pub yoffset: i16,
+    /// Size in nm
+    pub xsize: f64,
+    /// Size in nm
+    pub ysize: f64,
+    /// Offset in nm
+    pub xoffset: f64,
+    /// Offset in nm
+    pub yoffset: f64,
     pub zscale: i16,
     pub tilt: i16,
+    /// Scan duration in s
     pub speed: f64,
     pub line_time: f64,
     pub bias: f64,
     pub current: f64,
     pub sample: String,
     pub title: String,
+    /// Post-processing flags of the acquisition software, kept raw as the meaning of
+    /// the bits is not documented
     pub postpr: i16,
+    /// Parameter of the post-processing
     pub postd1: i16,
+    /// Scan mode, kept raw as the meaning of the values is not documented
     pub mode: i16,
     pub currfac: i16,
     pub num_pointscans: i16,
     pub unitnr: i16,
     pub version: i16,
     pub gain: i16,
+    /// Unused header values 48-59 and 61-63, kept for writing the file back
+    pub spare: [i16; 15],
+    pub point_scans: Vec<MulPointScan>,
     pub img_data: SpmImage,
 }
 
+/// Point scan (spectroscopy) recorded with an image, stored after its pixel data
+#[derive(Debug)]
+pub struct MulPointScan {
+    /// Number of data points
+    pub size: i16,
+    /// Kind of the recorded signal. Its scaling is not documented, so `spectrum` holds
+    /// the values as stored.
+    pub type_: i16,
+    /// Duration of the scan in s
+    pub time4scan: f64,
+    /// Start voltage of the sweep in mV
+    pub minv: f64,
+    /// End voltage of the sweep in mV
+    pub maxv: f64,
+    /// Position in nm
+    pub xpos: f64,
+    /// Position in nm
+    pub ypos: f64,
+    pub dz: i16,
+    pub delay: i16,
+    pub version: i16,
+    pub indendelay: i16,
+    /// End position in nm
+    pub xposend: f64,
+    /// End position in nm
+    pub yposend: f64,
+    pub vt_fw: i16,
+    pub it_fw: i16,
+    pub vt_bw: i16,
+    pub it_bw: i16,
+    pub lscan: i16,
+    pub spectrum: SpmSpectrum,
+}
+
 // Always length 21
 fn read_mul_string(cursor: &mut Cursor<&[u8]>) -> String {
     cursor.read_string(21)
@@ -85,187 +227,744 @@ fn read_data_points(buffer: &[u8]) -> Vec<f64> {
     data_points
 }
 
-fn read_point_scan(cursor: &mut Cursor<&[u8]>, num_data_points: i32) -> Vec<f64> {
-    let mut buffer = vec![0; (num_data_points * 2) as usize];
+fn read_point_scan(cursor: &mut Cursor<&[u8]>, num_data_points: usize) -> Vec<f64> {
+    let mut buffer = vec![0; num_data_points * 2];
     cursor.read_exact(&mut buffer).unwrap();
     read_data_points(&buffer)
 }
 
+// Header block of 18 values padded to MUL_BLOCK, followed by the data points
+fn read_mul_point_scan(cursor: &mut Cursor<&[u8]>, spec_id: String) -> MulPointScan {
+    let size = cursor.read_i16_le();
+    let type_ = cursor.read_i16_le();
+    let time4scan = f64::from(cursor.read_i16_le()) / 100.0; // in s
+    let minv = -f64::from(cursor.read_i16_le()) / 3.2768; // in mV
+    let maxv = -f64::from(cursor.read_i16_le()) / 3.2768; // in mV
+    let xpos = f64::from(cursor.read_i16_le()) / 10.0; // in nm
+    let ypos = f64::from(cursor.read_i16_le()) / 10.0; // in nm
+    let dz = cursor.read_i16_le();
+    let delay = cursor.read_i16_le();
+    let version = cursor.read_i16_le();
+    let indendelay = cursor.read_i16_le();
+    let xposend = f64::from(cursor.read_i16_le()) / 10.0; // in nm
+    let yposend = f64::from(cursor.read_i16_le()) / 10.0; // in nm
+    let vt_fw = cursor.read_i16_le();
+    let it_fw = cursor.read_i16_le();
+    let vt_bw = cursor.read_i16_le();
+    let it_bw = cursor.read_i16_le();
+    let lscan = cursor.read_i16_le();
+
+    let _ = cursor.seek(SeekFrom::Current((MUL_BLOCK - 18 * 2) as i64));
+
+    // A negative size in a broken file is read as no data points
+    let data = read_point_scan(cursor, size.max(0) as usize);
+
+    // Without a voltage sweep the signal is recorded over time
+    let (x_label, x_unit, x_data) = if minv != maxv {
+        ("Bias", "mV", linspace(minv, maxv, data.len()))
+    } else {
+        ("Time", "s", linspace(0.0, time4scan, data.len()))
+    };
+
+    MulPointScan {
+        size,
+        type_,
+        time4scan,
+        minv,
+        maxv,
+        xpos,
+        ypos,
+        dz,
+        delay,
+        version,
+        indendelay,
+        xposend,
+        yposend,
+        vt_fw,
+        it_fw,
+        vt_bw,
+        it_bw,
+        lscan,
+        spectrum: SpmSpectrum {
+            spec_id,
+            x_label: x_label.to_string(),
+            x_unit: x_unit.to_string(),
+            x_data,
+            channels: vec![SpectrumChannel {
+                name: "Signal".to_string(),
+                unit: "".to_string(),
+                data,
+            }],
+            xpos,
+            ypos,
+        },
+    }
+}
+
+/// Header of an image, stored in its first block
+struct MulHeader {
+    img_num: i16,
+    size: i16,
+    xres: usize,
+    yres: usize,
+    zres: i16,
+    datetime: DateTime<Utc>,
+    xsize: f64,
+    ysize: f64,
+    xoffset: f64,
+    yoffset: f64,
+    zscale: i16,
+    tilt: i16,
+    speed: f64,
+    bias: f64,
+    current: f64,
+    sample: String,
+    title: String,
+    postpr: i16,
+    postd1: i16,
+    mode: i16,
+    currfac: i16,
+    num_pointscans: i16,
+    unitnr: i16,
+    version: i16,
+    gain: i16,
+    spare: [i16; 15],
+}
+
+fn read_mul_header(cursor: &mut Cursor<&[u8]>, block: i32) -> Result<MulHeader, MulError> {
+    let img_num = cursor.read_i16_le();
+    let size = cursor.read_i16_le();
+
+    let xres = cursor.read_i16_le();
+    let yres = cursor.read_i16_le();
+    if xres <= 0 || yres <= 0 {
+        return Err(MulError::InvalidResolution {
+            img_num,
+            block,
+            xres,
+            yres,
+        });
+    }
+    let (xres, yres) = (xres as usize, yres as usize);
+    let zres = cursor.read_i16_le();
+
+    let year = cursor.read_i16_le();
+    let month = cursor.read_i16_le();
+    let day = cursor.read_i16_le();
+    let hour = cursor.read_i16_le();
+    let minute = cursor.read_i16_le();
+    let second = cursor.read_i16_le();
+
+    let xsize = f64::from(cursor.read_i16_le()) / 10.0; // in nm
+    let ysize = f64::from(cursor.read_i16_le()) / 10.0; // in nm
+
+    let xoffset = f64::from(cursor.read_i16_le()) / 10.0; // in nm
+    let yoffset = f64::from(cursor.read_i16_le()) / 10.0; // in nm
+
+    let zscale = cursor.read_i16_le();
+    let tilt = cursor.read_i16_le();
+    let speed = f64::from(cursor.read_i16_le()) / 100.0; // in s
+
+    let bias = f64::from(cursor.read_i16_le());
+    let current = cursor.read_i16_le();
+
+    let sample = read_mul_string(cursor);
+    let title = read_mul_string(cursor);
+
+    let postpr = cursor.read_i16_le();
+    let postd1 = cursor.read_i16_le();
+    let mode = cursor.read_i16_le();
+    let currfac = cursor.read_i16_le();
+    let num_pointscans = cursor.read_i16_le();
+    let unitnr = cursor.read_i16_le();
+    let version = cursor.read_i16_le();
+
+    let mut spare = [0_i16; 15];
+    for i in spare[..12].iter_mut() {
+        *i = cursor.read_i16_le();
+    }
+
+    let gain = cursor.read_i16_le();
+
+    for i in spare[12..].iter_mut() {
+        *i = cursor.read_i16_le();
+    }
+
+    let bias = -bias / 3.2768; //  in mV
+    let current = f64::from(current) * f64::from(currfac) * 0.01; // in nA
+
+    let datetime = Utc
+        .with_ymd_and_hms(
+            year.into(),
+            month as u32,
+            day as u32,
+            hour as u32,
+            minute as u32,
+            second as u32,
+        )
+        .single()
+        .ok_or(MulError::InvalidDatetime { img_num, block })?;
+
+    Ok(MulHeader {
+        img_num,
+        size,
+        xres,
+        yres,
+        zres,
+        datetime,
+        xsize,
+        ysize,
+        xoffset,
+        yoffset,
+        zscale,
+        tilt,
+        speed,
+        bias,
+        current,
+        sample,
+        title,
+        postpr,
+        postd1,
+        mode,
+        currfac,
+        num_pointscans,
+        unitnr,
+        version,
+        gain,
+        spare,
+    })
+}
+
+fn mul_img_id(filepath: &Path, img_num: i16) -> String {
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+    format!("{}_{}", basename, img_num)
+}
+
+// Reads header, pixel data and point scans of an image from its blocks
+fn read_mul_image_blocks(bytes: &[u8], block: i32, filepath: &Path) -> Result<MulImage, MulError> {
+    let mut cursor = Cursor::new(bytes);
+    let header = read_mul_header(&mut cursor, block)?;
+    let (xres, yres) = (header.xres, header.yres);
+
+    // Check the lengths given in the header, reading past the blocks would panic
+    let exceeds_size = MulError::DataExceedsSize {
+        img_num: header.img_num,
+        block,
+        size: header.size,
+    };
+    let mut end = xres
+        .checked_mul(yres)
+        .and_then(|n| n.checked_mul(2))
+        .and_then(|n| n.checked_add(MUL_BLOCK as usize))
+        .filter(|&end| end <= bytes.len())
+        .ok_or_else(|| exceeds_size.clone())?;
+
+    let img_data = read_mul_img_data(&mut cursor, xres * yres, header.zscale.into());
+
+    let img_id = mul_img_id(filepath, header.img_num);
+
+    let mut point_scans = Vec::with_capacity(header.num_pointscans.max(0) as usize);
+    for i in 0..header.num_pointscans {
+        if end + MUL_BLOCK as usize > bytes.len() {
+            return Err(exceeds_size);
+        }
+        let num_data_points = read_i16_le_bytes(&bytes[end..end + 2]).max(0) as usize;
+        end = end
+            .checked_add(MUL_BLOCK as usize + num_data_points * 2)
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| exceeds_size.clone())?;
+        let spec_id = format!("{}_ps{}", img_id, i + 1);
+        point_scans.push(read_mul_point_scan(&mut cursor, spec_id));
+    }
+
+    let line_time = header.speed / (yres as f64) * 1000.0; // in ms
+
+    let img_data = flip_img_data(img_data, xres as u32, yres as u32);
+
+    Ok(MulImage {
+        filepath: filepath.to_path_buf(),
+        img_num: header.img_num,
+        img_id: img_id.clone(),
+        size: header.size,
+        xres,
+        yres,
+        zres: header.zres,
+        datetime: header.datetime,
+        xsize: header.xsize,
+        ysize: header.ysize,
+        xoffset: header.xoffset,
+        yoffset: header.yoffset,
+        zscale: header.zscale,
+        tilt: header.tilt,
+        speed: header.speed,
+        line_time,
+        bias: header.bias,
+        current: header.current,
+        sample: header.sample,
+        title: header.title,
+        postpr: header.postpr,
+        postd1: header.postd1,
+        mode: header.mode,
+        currfac: header.currfac,
+        num_pointscans: header.num_pointscans,
+        unitnr: header.unitnr,
+        version: header.version,
+        gain: header.gain,
+        spare: header.spare,
+        point_scans,
+        img_data: SpmImage {
+            img_id,
+            xres,
+            yres,
+            xsize: header.xsize,
+            ysize: header.ysize,
+            z_unit: "nm".to_string(),
+            img_data,
+        },
+    })
+}
+
+// Files with an index start with the image number and the address of the first
+// image, which is always the block after the three index blocks
+fn first_image_block(first_block: &[u8]) -> i32 {
+    if first_block.len() < 6 {
+        return 0;
+    }
+    let mut cursor = Cursor::new(first_block);
+    let _nr = cursor.read_i16_le();
+    let adr = cursor.read_i32_le();
+    if adr == 3 {
+        adr
+    } else {
+        0
+    }
+}
+
 pub fn read_mul(filename: &str) -> Result<Vec<MulImage>> {
-    const MUL_BLOCK: i32 = 128;
-    let mut block_counter = 0;
+    match read_mul_partial(filename)? {
+        (mul, None) => Ok(mul),
+        (_, Some(err)) => Err(err.into()),
+    }
+}
+
+/// Reads all complete images of a MUL file, e.g. one still being written by the
+/// acquisition software. A truncated or broken tail is returned as error next to
+/// the images before it.
+pub fn read_mul_partial(filename: &str) -> Result<(Vec<MulImage>, Option<MulError>)> {
     let mut mul: Vec<MulImage> = Vec::new();
 
     let bytes = fs::read(filename)?;
     let file_len = bytes.len();
-    let mut cursor = Cursor::new(bytes.as_slice());
+    let filepath = PathBuf::from(&filename);
 
-    let _nr = cursor.read_i16_le();
-    let adr = cursor.read_i32_le();
+    let mut block_counter = first_image_block(&bytes) as usize;
 
-    if adr == 3 {
-        cursor
-            .seek(SeekFrom::Start((adr * MUL_BLOCK) as u64))
-            .expect("seeking to first block failed");
-        block_counter += adr;
-    } else {
-        cursor
-            .seek(SeekFrom::Start(0))
-            .expect("seeking to start failed");
+    while block_counter * (MUL_BLOCK as usize) < file_len {
+        let start = block_counter * MUL_BLOCK as usize;
+        if file_len - start < MUL_BLOCK as usize {
+            let err = MulError::TruncatedHeader {
+                block: block_counter as i32,
+                num_bytes: file_len - start,
+            };
+            return Ok((mul, Some(err)));
+        }
+
+        let img_num = read_i16_le_bytes(&bytes[start..start + 2]);
+        let size = read_i16_le_bytes(&bytes[start + 2..start + 4]);
+        if size <= 0 {
+            let err = MulError::InvalidSize {
+                img_num,
+                block: block_counter as i32,
+                size,
+            };
+            return Ok((mul, Some(err)));
+        }
+
+        let end = start + size as usize * MUL_BLOCK as usize;
+        if end > file_len {
+            let err = MulError::TruncatedImage {
+                img_num,
+                block: block_counter as i32,
+                expected_blocks: size,
+                available_blocks: ((file_len - start) / MUL_BLOCK as usize) as i32,
+            };
+            return Ok((mul, Some(err)));
+        }
+
+        match read_mul_image_blocks(&bytes[start..end], block_counter as i32, &filepath) {
+            Ok(img) => mul.push(img),
+            Err(err) => return Ok((mul, Some(err))),
+        }
+        block_counter += size as usize;
+    }
+    Ok((mul, None))
+}
+
+/// Header information of an image, for listing the content of a file without
+/// reading its pixel data
+#[derive(Debug)]
+pub struct MulIndexEntry {
+    pub img_num: i16,
+    pub img_id: String,
+    /// Number of the first block of the image in the file
+    pub block: i32,
+    /// Number of blocks of the image, including point scans
+    pub size: i16,
+    pub datetime: DateTime<Utc>,
+    /// Size in pixels
+    pub xres: usize,
+    /// Size in pixels
+    pub yres: usize,
+    /// Size in nm
+    pub xsize: f64,
+    /// Size in nm
+    pub ysize: f64,
+    /// Bias in mV
+    pub bias: f64,
+    /// Current in nA
+    pub current: f64,
+    pub num_pointscans: i16,
+}
+
+fn read_mul_blocks(file: &mut fs::File, block: i32, num_blocks: i32) -> Result<Vec<u8>> {
+    let mut buffer = vec![0; num_blocks as usize * MUL_BLOCK as usize];
+    file.seek(SeekFrom::Start(block as u64 * MUL_BLOCK as u64))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Lists the images of a MUL file by reading only their header blocks and seeking
+/// from image to image using their size. Like `read_mul_partial`, it stops at the
+/// first truncated or broken image and returns the error next to the entries before
+/// it.
+pub fn read_mul_index(filename: &str) -> Result<(Vec<MulIndexEntry>, Option<MulError>)> {
+    let mut file = fs::File::open(filename)?;
+    let file_len = file.metadata()?.len();
+    let filepath = PathBuf::from(&filename);
+
+    let mut index = Vec::new();
+    if file_len < MUL_BLOCK as u64 {
+        return Ok((index, None));
     }
+    let mut block = first_image_block(&read_mul_blocks(&mut file, 0, 1)?);
+
+    while block as u64 * (MUL_BLOCK as u64) < file_len {
+        let start = block as u64 * MUL_BLOCK as u64;
+        if file_len - start < MUL_BLOCK as u64 {
+            let err = MulError::TruncatedHeader {
+                block,
+                num_bytes: (file_len - start) as usize,
+            };
+            return Ok((index, Some(err)));
+        }
+        let buffer = read_mul_blocks(&mut file, block, 1)?;
+        let header = match read_mul_header(&mut Cursor::new(buffer.as_slice()), block) {
+            Ok(header) => header,
+            Err(err) => return Ok((index, Some(err))),
+        };
+        if header.size <= 0 {
+            let err = MulError::InvalidSize {
+                img_num: header.img_num,
+                block,
+                size: header.size,
+            };
+            return Ok((index, Some(err)));
+        }
+        if start + header.size as u64 * MUL_BLOCK as u64 > file_len {
+            let err = MulError::TruncatedImage {
+                img_num: header.img_num,
+                block,
+                expected_blocks: header.size,
+                available_blocks: ((file_len - start) / MUL_BLOCK as u64) as i32,
+            };
+            return Ok((index, Some(err)));
+        }
+
+        index.push(MulIndexEntry {
+            img_num: header.img_num,
+            img_id: mul_img_id(&filepath, header.img_num),
+            block,
+            size: header.size,
+            datetime: header.datetime,
+            xres: header.xres,
+            yres: header.yres,
+            xsize: header.xsize,
+            ysize: header.ysize,
+            bias: header.bias,
+            current: header.current,
+            num_pointscans: header.num_pointscans,
+        });
+        block += i32::from(header.size);
+    }
+    Ok((index, None))
+}
 
-    while block_counter * MUL_BLOCK < file_len as i32 {
-        let img_num = cursor.read_i16_le();
-        let size = cursor.read_i16_le();
-
-        // we know these must be positive
-        let xres = cursor.read_i16_le() as usize;
-        let yres = cursor.read_i16_le() as usize;
-        let zres = cursor.read_i16_le();
-
-        let year = cursor.read_i16_le();
-        let month = cursor.read_i16_le();
-        let day = cursor.read_i16_le();
-        let hour = cursor.read_i16_le();
-        let minute = cursor.read_i16_le();
-        let second = cursor.read_i16_le();
-
-        let xsize = cursor.read_i16_le() / 10; // in nm
-        let ysize = cursor.read_i16_le() / 10; // in nm
-
-        let xoffset = cursor.read_i16_le() / 10; // in nm
-        let yoffset = cursor.read_i16_le() / 10; // in nm
-
-        let zscale = cursor.read_i16_le();
-        let tilt = cursor.read_i16_le();
-        let speed = cursor.read_i16_le() / 100; // in s
-
-        let bias = f64::from(cursor.read_i16_le());
-        let current = cursor.read_i16_le();
-
-        let sample = read_mul_string(&mut cursor);
-        let title = read_mul_string(&mut cursor);
-
-        let postpr = cursor.read_i16_le();
-        let postd1 = cursor.read_i16_le();
-        let mode = cursor.read_i16_le();
-        let currfac = cursor.read_i16_le();
-        let num_pointscans = cursor.read_i16_le();
-        let unitnr = cursor.read_i16_le();
-        let version = cursor.read_i16_le();
-
-        let _spare_48 = cursor.read_i16_le();
-        let _spare_49 = cursor.read_i16_le();
-        let _spare_50 = cursor.read_i16_le();
-        let _spare_51 = cursor.read_i16_le();
-        let _spare_52 = cursor.read_i16_le();
-        let _spare_53 = cursor.read_i16_le();
-        let _spare_54 = cursor.read_i16_le();
-        let _spare_54 = cursor.read_i16_le();
-        let _spare_56 = cursor.read_i16_le();
-        let _spare_57 = cursor.read_i16_le();
-        let _spare_58 = cursor.read_i16_le();
-        let _spare_59 = cursor.read_i16_le();
-
-        let gain = cursor.read_i16_le();
-
-        let _spare_61 = cursor.read_i16_le();
-        let _spare_62 = cursor.read_i16_le();
-        let _spare_63 = cursor.read_i16_le();
-
-        let img_data = read_mul_img_data(&mut cursor, xres * yres, zscale.into());
-
-        if num_pointscans > 0 {
-            for _ in 0..num_pointscans {
-                let _ps_size = cursor.read_i16_le();
-                let _ps_type = cursor.read_i16_le();
-                let _ps_time4scan = cursor.read_i16_le();
-                let _ps_minv = cursor.read_i16_le();
-                let _ps_maxv = cursor.read_i16_le();
-                let _ps_xpos = cursor.read_i16_le();
-                let _ps_ypos = cursor.read_i16_le();
-                let _ps_dz = cursor.read_i16_le();
-                let _ps_delay = cursor.read_i16_le();
-                let _ps_version = cursor.read_i16_le();
-                let _ps_indendelay = cursor.read_i16_le();
-                let _ps_xposend = cursor.read_i16_le();
-                let _ps_yposend = cursor.read_i16_le();
-                let _ps_vt_fw = cursor.read_i16_le();
-                let _ps_it_fw = cursor.read_i16_le();
-                let _ps_vt_bw = cursor.read_i16_le();
-                let _ps_it_bw = cursor.read_i16_le();
-                let _ps_lscan = cursor.read_i16_le();
-
-                let _ = cursor.seek(SeekFrom::Current((MUL_BLOCK - 18 * 2) as i64));
-
-                let _ps_data = read_point_scan(&mut cursor, _ps_size as i32);
-            }
+/// Reads the single image with number `img_num`, only decoding its own blocks
+pub fn read_mul_image(filename: &str, img_num: i16) -> Result<MulImage> {
+    let (index, err) = read_mul_index(filename)?;
+    let entry = match index.into_iter().find(|entry| entry.img_num == img_num) {
+        Some(entry) => entry,
+        None => {
+            return Err(match err {
+                Some(err) => err.into(),
+                None => anyhow!("No image with number {} in {}", img_num, filename),
+            })
         }
+    };
 
-        let line_time = f64::from(speed) / (yres as f64) * 1000.0; // in ms
-        let bias = -bias / 3.2768; //  in mV
-        let current = f64::from(current) * f64::from(currfac) * 0.01; // in nA
-
-        let datetime = Utc
-            .with_ymd_and_hms(
-                year.into(),
-                month as u32,
-                day as u32,
-                hour as u32,
-                minute as u32,
-                second as u32,
-            )
-            .single()
-            .expect("Parsing datetime failed");
-
-        let filepath = PathBuf::from(&filename);
-        let basename = filepath.file_stem().unwrap().to_str().unwrap();
-        let img_id = format!("{}_{}", basename, img_num);
-        let img_data = flip_img_data(img_data, xres as u32, yres as u32);
-
-        block_counter += size as i32;
-
-        mul.push(MulImage {
-            filepath,
+    let mut file = fs::File::open(filename)?;
+    let buffer = read_mul_blocks(&mut file, entry.block, entry.size.into())?;
+    Ok(read_mul_image_blocks(
+        &buffer,
+        entry.block,
+        &PathBuf::from(&filename),
+    )?)
+}
+
+/// Rounds a value in the units of the file, values outside of i16 would be saturated
+fn to_mul_i16(value: f64, name: &str, img_num: i16) -> Result<i16> {
+    let raw = value.round();
+    if raw.is_finite() && (f64::from(i16::MIN)..=f64::from(i16::MAX)).contains(&raw) {
+        Ok(raw as i16)
+    } else {
+        Err(anyhow!(
+            "{} of image {} is out of the range of MUL files: {}",
+            name,
             img_num,
-            img_id: img_id.clone(),
-            size,
-            xres,
-            yres,
-            zres,
-            datetime,
-            xsize,
-            ysize,
-            xoffset,
-            yoffset,
-            zscale,
-            tilt,
-            speed: speed as f64,
-            line_time,
-            bias,
-            current,
-            sample,
-            title,
-            postpr,
-            postd1,
-            mode,
-            currfac,
-            num_pointscans,
-            unitnr,
-            version,
-            gain,
-            img_data: SpmImage {
-                img_id,
-                xres,
-                yres,
-                xsize: xsize as f64,
-                ysize: ysize as f64,
-                img_data,
-            },
-        })
+            value
+        ))
+    }
+}
+
+fn len_to_mul_i16(len: usize, name: &str, img_num: i16) -> Result<i16> {
+    i16::try_from(len).map_err(|_| {
+        anyhow!(
+            "{} of image {} is out of the range of MUL files: {}",
+            name,
+            img_num,
+            len
+        )
+    })
+}
+
+// Inverse of read_mul_pixels
+fn write_mul_pixels(buffer: &mut Vec<u8>, pixels: &[f64], zscale: i32, img_num: i16) -> Result<()> {
+    if zscale == 0 && pixels.iter().any(|&pixel| pixel != 0.0) {
+        return Err(anyhow!("Image {} has a z scale of 0", img_num));
+    }
+    for pixel in pixels {
+        let raw = if zscale != 0 {
+            pixel * 2000.0 / f64::from(zscale) * 1.36 / -0.1
+        } else {
+            0.0
+        };
+        buffer.write_i16_le(to_mul_i16(raw, "Pixel", img_num)?);
     }
-    assert_eq! {block_counter * MUL_BLOCK, bytes.len() as i32};
-    Ok(mul)
+    Ok(())
+}
+
+/// Data points of a point scan, the signal with as many values as the x axis
+fn point_scan_data(point_scan: &MulPointScan, img_num: i16) -> Result<&[f64]> {
+    let spectrum = &point_scan.spectrum;
+    let data = spectrum
+        .channels
+        .first()
+        .map(|channel| channel.data.as_slice())
+        .unwrap_or_default();
+    if data.len() != spectrum.x_data.len() {
+        return Err(anyhow!(
+            "Point scan {} of image {} has {} data points for {} x values",
+            spectrum.spec_id,
+            img_num,
+            data.len(),
+            spectrum.x_data.len()
+        ));
+    }
+    Ok(data)
+}
+
+fn write_mul_point_scan(
+    buffer: &mut Vec<u8>,
+    img: &MulImage,
+    point_scan: &MulPointScan,
+) -> Result<()> {
+    let img_num = img.img_num;
+    let data = point_scan_data(point_scan, img_num)?;
+
+    buffer.write_i16_le(len_to_mul_i16(data.len(), "Point scan size", img_num)?);
+    buffer.write_i16_le(point_scan.type_);
+    buffer.write_i16_le(to_mul_i16(
+        point_scan.time4scan * 100.0,
+        "Point scan time",
+        img_num,
+    )?);
+    buffer.write_i16_le(to_mul_i16(
+        -point_scan.minv * 3.2768,
+        "Point scan voltage",
+        img_num,
+    )?);
+    buffer.write_i16_le(to_mul_i16(
+        -point_scan.maxv * 3.2768,
+        "Point scan voltage",
+        img_num,
+    )?);
+    buffer.write_i16_le(to_mul_i16(
+        point_scan.xpos * 10.0,
+        "Point scan position",
+        img_num,
+    )?);
+    buffer.write_i16_le(to_mul_i16(
+        point_scan.ypos * 10.0,
+        "Point scan position",
+        img_num,
+    )?);
+    buffer.write_i16_le(point_scan.dz);
+    buffer.write_i16_le(point_scan.delay);
+    buffer.write_i16_le(point_scan.version);
+    buffer.write_i16_le(point_scan.indendelay);
+    buffer.write_i16_le(to_mul_i16(
+        point_scan.xposend * 10.0,
+        "Point scan position",
+        img_num,
+    )?);
+    buffer.write_i16_le(to_mul_i16(
+        point_scan.yposend * 10.0,
+        "Point scan position",
+        img_num,
+    )?);
+    buffer.write_i16_le(point_scan.vt_fw);
+    buffer.write_i16_le(point_scan.it_fw);
+    buffer.write_i16_le(point_scan.vt_bw);
+    buffer.write_i16_le(point_scan.it_bw);
+    buffer.write_i16_le(point_scan.lscan);
+    buffer.resize(buffer.len() + (MUL_BLOCK - 18 * 2) as usize, 0);
+
+    for x in data {
+        buffer.write_i16_le(to_mul_i16(*x, "Point scan data", img_num)?);
+    }
+    Ok(())
+}
+
+fn write_mul_image(buffer: &mut Vec<u8>, img: &MulImage) -> Result<()> {
+    let img_num = img.img_num;
+    let pixels = &img.img_data.img_data;
+    if pixels.len() != img.xres * img.yres {
+        return Err(anyhow!(
+            "Image {} has {} pixels, expected {}x{}",
+            img_num,
+            pixels.len(),
+            img.xres,
+            img.yres
+        ));
+    }
+
+    let start = buffer.len();
+    let mut num_bytes = MUL_BLOCK as usize + pixels.len() * 2;
+    for point_scan in img.point_scans.iter() {
+        num_bytes += MUL_BLOCK as usize + point_scan_data(point_scan, img_num)?.len() * 2;
+    }
+    let size = num_bytes.div_ceil(MUL_BLOCK as usize);
+
+    let currfac = if img.currfac != 0 {
+        f64::from(img.currfac)
+    } else {
+        1.0
+    };
+
+    buffer.write_i16_le(img_num);
+    buffer.write_i16_le(len_to_mul_i16(size, "Number of blocks", img_num)?);
+    buffer.write_i16_le(len_to_mul_i16(img.xres, "Number of pixels", img_num)?);
+    buffer.write_i16_le(len_to_mul_i16(img.yres, "Number of lines", img_num)?);
+    buffer.write_i16_le(img.zres);
+
+    buffer.write_i16_le(to_mul_i16(img.datetime.year().into(), "Year", img_num)?);
+    buffer.write_i16_le(img.datetime.month() as i16);
+    buffer.write_i16_le(img.datetime.day() as i16);
+    buffer.write_i16_le(img.datetime.hour() as i16);
+    buffer.write_i16_le(img.datetime.minute() as i16);
+    buffer.write_i16_le(img.datetime.second() as i16);
+
+    buffer.write_i16_le(to_mul_i16(img.xsize * 10.0, "Size", img_num)?);
+    buffer.write_i16_le(to_mul_i16(img.ysize * 10.0, "Size", img_num)?);
+    buffer.write_i16_le(to_mul_i16(img.xoffset * 10.0, "Offset", img_num)?);
+    buffer.write_i16_le(to_mul_i16(img.yoffset * 10.0, "Offset", img_num)?);
+
+    buffer.write_i16_le(img.zscale);
+    buffer.write_i16_le(img.tilt);
+    buffer.write_i16_le(to_mul_i16(img.speed * 100.0, "Scan duration", img_num)?);
+
+    buffer.write_i16_le(to_mul_i16(-img.bias * 3.2768, "Bias", img_num)?);
+    buffer.write_i16_le(to_mul_i16(
+        img.current / currfac / 0.01,
+        "Current",
+        img_num,
+    )?);
+
+    buffer.write_string(&img.sample, 21);
+    buffer.write_string(&img.title, 21);
+
+    buffer.write_i16_le(img.postpr);
+    buffer.write_i16_le(img.postd1);
+    buffer.write_i16_le(img.mode);
+    buffer.write_i16_le(img.currfac);
+    buffer.write_i16_le(len_to_mul_i16(
+        img.point_scans.len(),
+        "Number of point scans",
+        img_num,
+    )?);
+    buffer.write_i16_le(img.unitnr);
+    buffer.write_i16_le(img.version);
+
+    for i in img.spare[..12].iter() {
+        buffer.write_i16_le(*i);
+    }
+    buffer.write_i16_le(img.gain);
+    for i in img.spare[12..].iter() {
+        buffer.write_i16_le(*i);
+    }
+
+    let unflipped = flip_img_data(pixels.clone(), img.xres as u32, img.yres as u32);
+    write_mul_pixels(buffer, &unflipped, img.zscale.into(), img_num)?;
+
+    for point_scan in img.point_scans.iter() {
+        write_mul_point_scan(buffer, img, point_scan)?;
+    }
+
+    buffer.resize(start + size * MUL_BLOCK as usize, 0);
+    Ok(())
+}
+
+/// Writes images to a MUL file. The file starts with an index of image numbers and
+/// block addresses in the first three blocks, followed by the images.
+pub fn write_mul(filename: &str, images: &[MulImage]) -> Result<()> {
+    const INDEX_BLOCKS: i32 = 3;
+    const INDEX_ENTRY_SIZE: usize = 6;
+    let max_images = (INDEX_BLOCKS * MUL_BLOCK) as usize / INDEX_ENTRY_SIZE - 1;
+    if images.len() > max_images {
+        return Err(anyhow!(
+            "MUL files can hold at most {} images, got {}",
+            max_images,
+            images.len()
+        ));
+    }
+
+    let mut index: Vec<u8> = Vec::with_capacity((INDEX_BLOCKS * MUL_BLOCK) as usize);
+    let mut buffer: Vec<u8> = Vec::new();
+
+    for img in images {
+        let adr = INDEX_BLOCKS + (buffer.len() / MUL_BLOCK as usize) as i32;
+        index.write_i16_le(img.img_num);
+        index.write_i32_le(adr);
+        write_mul_image(&mut buffer, img)?;
+    }
+    // The index ends with the next free block address
+    index.write_i16_le(0);
+    index.write_i32_le(INDEX_BLOCKS + (buffer.len() / MUL_BLOCK as usize) as i32);
+    index.resize((INDEX_BLOCKS * MUL_BLOCK) as usize, 0);
+
+    index.append(&mut buffer);
+    fs::write(filename, index)?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -281,4 +980,48 @@ mod tests {
         let mut cursor = Cursor::new(buffer.as_slice());
         assert_eq!(read_mul_string(&mut cursor), s);
     }
+
+    fn point_scan_bytes(header: [i16; 18], data: &[i16]) -> Vec<u8> {
+        let mut buffer: Vec<u8> = header.iter().flat_map(|x| x.to_le_bytes()).collect();
+        buffer.resize(MUL_BLOCK as usize, 0);
+        buffer.extend(data.iter().flat_map(|x| x.to_le_bytes()));
+        buffer
+    }
+
+    #[test]
+    fn test_read_mul_point_scan_voltage() {
+        let header = [
+            3, 1, 50, 3277, -3277, 100, -200, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let buffer = point_scan_bytes(header, &[10, 20, 30]);
+        let mut cursor = Cursor::new(buffer.as_slice());
+        let ps = read_mul_point_scan(&mut cursor, "ps".to_string());
+        assert_eq!(cursor.position(), MUL_BLOCK as u64 + 6);
+        assert_eq!((ps.xpos, ps.ypos), (10.0, -20.0));
+        assert_eq!(ps.spectrum.x_label, "Bias");
+        let x: Vec<_> = ps.spectrum.x_data.iter().map(|x| x.round()).collect();
+        assert_eq!(x, vec![-1000.0, 0.0, 1000.0]);
+        assert_eq!(ps.spectrum.channels[0].data, vec![10.0, 20.0, 30.0]);
+    }
+
+    #[test]
+    fn test_read_mul_point_scan_time() {
+        let header = [2, 2, 150, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        let buffer = point_scan_bytes(header, &[1360, -2720]);
+        let mut cursor = Cursor::new(buffer.as_slice());
+        let ps = read_mul_point_scan(&mut cursor, "ps".to_string());
+        assert_eq!(ps.spectrum.x_label, "Time");
+        assert_eq!(ps.spectrum.x_data, vec![0.0, 1.5]);
+        assert_eq!(ps.spectrum.channels[0].data, vec![1360.0, -2720.0]);
+    }
+
+    #[test]
+    fn test_read_mul_point_scan_negative_size() {
+        let header = [-5, 1, 50, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        let buffer = point_scan_bytes(header, &[]);
+        let mut cursor = Cursor::new(buffer.as_slice());
+        let ps = read_mul_point_scan(&mut cursor, "ps".to_string());
+        assert!(ps.spectrum.x_data.is_empty());
+        assert!(ps.spectrum.channels[0].data.is_empty());
+    }
 }
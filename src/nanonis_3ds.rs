@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use ndarray::{s, Array2, Array3};
+
+use crate::nanonis_dat::parse_column_label;
+use crate::nanonis_sxm::parse_datetime;
+use crate::spm_image::SpmImage;
+use crate::spm_spectrum::{linspace, SpectrumChannel, SpmSpectrum};
+
+/// Nanonis grid spectroscopy (`.3ds`), a text header followed by the parameters and
+/// spectra of each grid point as big-endian float32
+#[derive(Debug)]
+pub struct Nanonis3ds {
+    pub filepath: PathBuf,
+    pub grid_id: String,
+    /// E.g. `Grid Spectroscopy`
+    pub experiment: String,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Number of grid points
+    pub xres: usize,
+    /// Number of grid lines
+    pub yres: usize,
+    /// Center of the grid in nm
+    pub xcenter: f64,
+    /// Center of the grid in nm
+    pub ycenter: f64,
+    /// Size in nm
+    pub xsize: f64,
+    /// Size in nm
+    pub ysize: f64,
+    /// Rotation in degrees
+    pub angle: f64,
+    pub comment: String,
+    /// All `Key=value` pairs of the header, without quotes
+    pub header: BTreeMap<String, String>,
+    /// Swept signal, e.g. `Bias`
+    pub sweep_label: String,
+    pub sweep_unit: String,
+    /// Sweep axis, from `Sweep Start` to `Sweep End` of the first grid point
+    pub sweep: Vec<f64>,
+    /// Labels of the fixed and experiment parameters, e.g. `X (m)`
+    pub param_names: Vec<String>,
+    /// Parameters of each grid point with shape (yres, xres, param_names.len())
+    pub params: Array3<f64>,
+    pub channels: Vec<GridChannel>,
+}
+
+/// One measured signal at every grid point
+#[derive(Debug)]
+pub struct GridChannel {
+    pub name: String,
+    pub unit: String,
+    /// Spectra with shape (yres, xres, sweep.len())
+    pub data: Array3<f64>,
+}
+
+impl Nanonis3ds {
+    pub fn channel(&self, name: &str) -> Option<&GridChannel> {
+        self.channels.iter().find(|c| c.name == name)
+    }
+
+    /// Value of a parameter, e.g. `Z (m)`, at every grid point
+    pub fn param(&self, name: &str) -> Option<Array2<f64>> {
+        let index = self.param_names.iter().position(|n| n == name)?;
+        Some(self.params.slice(s![.., .., index]).to_owned())
+    }
+
+    /// Spectrum at a grid point, with its position from the `X (m)` and `Y (m)` parameters
+    pub fn spectrum(&self, x: usize, y: usize) -> Option<SpmSpectrum> {
+        if x >= self.xres || y >= self.yres {
+            return None;
+        }
+        let position = |name: &str| {
+            self.param(name)
+                .map(|values| values[[y, x]] * 1e9)
+                .unwrap_or_default()
+        };
+        Some(SpmSpectrum {
+            spec_id: format!("{}_{}_{}", self.grid_id, x, y),
+            x_label: self.sweep_label.clone(),
+            x_unit: self.sweep_unit.clone(),
+            x_data: self.sweep.clone(),
+            channels: self
+                .channels
+                .iter()
+                .map(|channel| SpectrumChannel {
+                    name: channel.name.clone(),
+                    unit: channel.unit.clone(),
+                    data: channel.data.slice(s![y, x, ..]).to_vec(),
+                })
+                .collect(),
+            xpos: position("X (m)"),
+            ypos: position("Y (m)"),
+        })
+    }
+
+    /// Map of a channel at one point of the sweep, e.g. a dI/dV map at one bias.
+    /// The lines are in the order they were measured.
+    pub fn map(&self, name: &str, index: usize) -> Option<SpmImage> {
+        let channel = self.channel(name)?;
+        if index >= self.sweep.len() {
+            return None;
+        }
+        Some(SpmImage {
+            img_id: format!("{}_{}_{}", self.grid_id, name, index),
+            xres: self.xres,
+            yres: self.yres,
+            xsize: self.xsize,
+            ysize: self.ysize,
+            z_unit: channel.unit.clone(),
+            img_data: channel
+                .data
+                .slice(s![.., .., index])
+                .iter()
+                .copied()
+                .collect(),
+        })
+    }
+}
+
+const HEADER_END: &[u8] = b":HEADER_END:";
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .filter(|x| !x.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_3ds_datetime(value: &str) -> Option<DateTime<Utc>> {
+    let (date, time) = value.split_once(' ')?;
+    parse_datetime(date, time).ok()
+}
+
+pub fn read_3ds(filename: &str) -> Result<Nanonis3ds> {
+    let bytes = fs::read(filename)?;
+
+    let header_end = bytes
+        .windows(HEADER_END.len())
+        .position(|window| window == HEADER_END)
+        .ok_or_else(|| anyhow!("No header end found in {}", filename))?;
+    let header: BTreeMap<String, String> = String::from_utf8_lossy(&bytes[..header_end])
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.trim().trim_matches('"').to_string()))
+        .collect();
+
+    // The data starts after the line break following the marker, further bytes of
+    // 0x0D or 0x0A belong to the first value
+    let mut data_start = header_end + HEADER_END.len();
+    if bytes[data_start..].starts_with(b"\r\n") {
+        data_start += 2;
+    } else if bytes[data_start..].starts_with(b"\n") {
+        data_start += 1;
+    }
+    let data = &bytes[data_start..];
+
+    let get = |key: &str| header.get(key).map(String::as_str).unwrap_or_default();
+
+    let (xres, yres): (usize, usize) = get("Grid dim")
+        .split_once('x')
+        .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+        .ok_or_else(|| anyhow!("Invalid grid dim `{}`", get("Grid dim")))?;
+
+    let settings: Vec<f64> = get("Grid settings")
+        .split(';')
+        .map(|x| x.parse().unwrap_or_default())
+        .collect();
+    let setting = |i: usize| settings.get(i).copied().unwrap_or_default();
+
+    let num_points: usize = get("Points").parse()?;
+    let num_params: usize = get("# Parameters (4 byte)").parse()?;
+
+    let mut param_names = split_list(get("Fixed parameters"));
+    param_names.extend(split_list(get("Experiment parameters")));
+    param_names.resize_with(num_params, String::new);
+    for (i, name) in param_names.iter_mut().enumerate() {
+        if name.is_empty() {
+            *name = format!("Parameter{}", i);
+        }
+    }
+
+    let channel_labels = split_list(get("Channels"));
+    let (sweep_label, sweep_unit) = parse_column_label(get("Sweep Signal"));
+
+    // A grid stopped early has fewer points than announced, these stay NaN. It must
+    // hold at least its first line, so that a broken header cannot make the grid far
+    // larger than the data.
+    let num_values = data.len() / 4;
+    let too_large = || {
+        anyhow!(
+            "Grid of {}x{} points with {} values each is too large for {} values in the file",
+            xres,
+            yres,
+            num_params.saturating_add(channel_labels.len().saturating_mul(num_points)),
+            num_values
+        )
+    };
+    let point_len = channel_labels
+        .len()
+        .checked_mul(num_points)
+        .and_then(|n| n.checked_add(num_params))
+        .ok_or_else(too_large)?;
+    let line_len = xres.checked_mul(point_len).ok_or_else(too_large)?;
+    let grid_len = line_len.checked_mul(yres).ok_or_else(too_large)?;
+    if num_values < line_len.min(grid_len) {
+        return Err(too_large());
+    }
+    let mut values: Vec<f64> = data
+        .chunks_exact(4)
+        .map(|x| f64::from(f32::from_be_bytes([x[0], x[1], x[2], x[3]])))
+        .collect();
+    values.resize(grid_len, f64::NAN);
+    let values = Array3::from_shape_vec((yres, xres, point_len), values)?;
+
+    let params = values.slice(s![.., .., ..num_params]).to_owned();
+    let channels = channel_labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| {
+            let (name, unit) = parse_column_label(label);
+            let start = num_params + i * num_points;
+            GridChannel {
+                name,
+                unit,
+                data: values
+                    .slice(s![.., .., start..start + num_points])
+                    .to_owned(),
+            }
+        })
+        .collect();
+
+    let first_param = |name: &str| {
+        param_names
+            .iter()
+            .position(|n| n == name)
+            .and_then(|i| params.get([0, 0, i]).copied())
+    };
+    let sweep = match (first_param("Sweep Start"), first_param("Sweep End")) {
+        (Some(start), Some(end)) => linspace(start, end, num_points),
+        _ => (0..num_points).map(|i| i as f64).collect(),
+    };
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+
+    Ok(Nanonis3ds {
+        grid_id: basename.to_string(),
+        filepath,
+        experiment: get("Experiment").to_string(),
+        start_time: parse_3ds_datetime(get("Start time")),
+        end_time: parse_3ds_datetime(get("End time")),
+        xres,
+        yres,
+        xcenter: setting(0) * 1e9,
+        ycenter: setting(1) * 1e9,
+        xsize: setting(2) * 1e9,
+        ysize: setting(3) * 1e9,
+        angle: setting(4),
+        comment: get("Comment").to_string(),
+        sweep_label,
+        sweep_unit,
+        sweep,
+        param_names,
+        params,
+        channels,
+        header,
+    })
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+use crate::nanonis_sxm::parse_datetime;
+use crate::spm_spectrum::{SpectrumChannel, SpmSpectrum};
+
+/// Nanonis spectroscopy or history data (`.dat`), a tab separated key/value header
+/// followed by the columns of the `[DATA]` section
+#[derive(Debug)]
+pub struct NanonisDat {
+    pub filepath: PathBuf,
+    /// E.g. `bias spectroscopy`, `Z spectroscopy` or `History Data`
+    pub experiment: String,
+    pub datetime: Option<DateTime<Utc>>,
+    /// All key/value pairs of the header
+    pub header: BTreeMap<String, String>,
+    /// Whether a backward sweep was recorded
+    pub backward_sweep: bool,
+    /// Number of sweeps averaged into each point
+    pub num_sweeps: usize,
+    /// Description of each channel of `spectrum`, in the same order
+    pub channel_info: Vec<NanonisChannelInfo>,
+    /// First column as x axis, all other columns as channels
+    pub spectrum: SpmSpectrum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SweepDirection {
+    Forward,
+    Backward,
+}
+
+/// Flags of a column label like `Current [bwd] (A)` or `Current [00002] (A)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NanonisChannelInfo {
+    /// Name without the flags, e.g. `Current`
+    pub signal: String,
+    pub direction: SweepDirection,
+    /// Average over all sweeps
+    pub average: bool,
+    /// Number of the sweep for data saved per sweep, starting at 1
+    pub sweep: Option<usize>,
+}
+
+impl NanonisChannelInfo {
+    fn from_name(name: &str) -> Self {
+        let mut info = NanonisChannelInfo {
+            signal: String::new(),
+            direction: SweepDirection::Forward,
+            average: false,
+            sweep: None,
+        };
+        let mut signal = Vec::new();
+        for word in name.split(' ') {
+            match word.strip_prefix('[').and_then(|w| w.strip_suffix(']')) {
+                Some("bwd") => info.direction = SweepDirection::Backward,
+                Some("AVG") => info.average = true,
+                Some(flag) if flag.parse::<usize>().is_ok() => info.sweep = flag.parse().ok(),
+                _ => signal.push(word),
+            }
+        }
+        info.signal = signal.join(" ");
+        info
+    }
+}
+
+/// Splits a column label like `Bias calc (V)` into name and unit
+pub(crate) fn parse_column_label(label: &str) -> (String, String) {
+    match label.strip_suffix(')').and_then(|l| l.rsplit_once(" (")) {
+        Some((name, unit)) => (name.to_string(), unit.to_string()),
+        None => (label.to_string(), String::new()),
+    }
+}
+
+const DATA_SECTION: &str = "[DATA]";
+
+pub fn read_nanonis_dat(filename: &str) -> Result<NanonisDat> {
+    let bytes = fs::read(filename)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let mut lines = text.lines();
+
+    let mut header = BTreeMap::new();
+    for line in lines.by_ref() {
+        if line.trim_end() == DATA_SECTION {
+            break;
+        }
+        // Empty values are kept, e.g. `User\t\t`
+        if let Some((key, value)) = line.split_once('\t') {
+            header.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    let labels: Vec<&str> = lines
+        .next()
+        .ok_or_else(|| anyhow!("No {} section in {}", DATA_SECTION, filename))?
+        .trim_end()
+        .split('\t')
+        .collect();
+
+    let mut columns: Vec<Vec<f64>> = vec![Vec::new(); labels.len()];
+    for line in lines {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let values: Vec<&str> = line.split('\t').collect();
+        if values.len() != labels.len() {
+            return Err(anyhow!(
+                "Expected {} values, got {} in line `{}`",
+                labels.len(),
+                values.len(),
+                line
+            ));
+        }
+        for (column, value) in columns.iter_mut().zip(values) {
+            column.push(value.trim().parse()?);
+        }
+    }
+
+    let mut columns = labels.iter().zip(columns).map(|(label, data)| {
+        let (name, unit) = parse_column_label(label);
+        SpectrumChannel { name, unit, data }
+    });
+    let x = columns
+        .next()
+        .ok_or_else(|| anyhow!("No columns in {}", filename))?;
+    let channels: Vec<SpectrumChannel> = columns.collect();
+    let channel_info = channels
+        .iter()
+        .map(|channel| NanonisChannelInfo::from_name(&channel.name))
+        .collect();
+
+    let get = |key: &str| header.get(key).map(String::as_str).unwrap_or_default();
+    // Parameters of the experiment are prefixed, e.g. `Bias Spectroscopy>backward sweep`
+    let get_setting = |setting: &str| {
+        header
+            .iter()
+            .find(|(key, _)| key.rsplit('>').next() == Some(setting))
+            .map(|(_, value)| value.as_str())
+    };
+
+    let backward_sweep = match get_setting("backward sweep") {
+        Some(value) => value == "TRUE",
+        None => channels.iter().any(|c| c.name.contains("[bwd]")),
+    };
+    let num_sweeps = get_setting("Number of sweeps")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(1);
+
+    let datetime = get("Date")
+        .split_once(' ')
+        .and_then(|(date, time)| parse_datetime(date, time).ok());
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+
+    let spectrum = SpmSpectrum {
+        spec_id: basename.to_string(),
+        x_label: x.name,
+        x_unit: x.unit,
+        x_data: x.data,
+        channels,
+        xpos: get("X (m)").parse::<f64>().unwrap_or_default() * 1e9,
+        ypos: get("Y (m)").parse::<f64>().unwrap_or_default() * 1e9,
+    };
+
+    Ok(NanonisDat {
+        filepath,
+        experiment: get("Experiment").to_string(),
+        datetime,
+        header,
+        backward_sweep,
+        num_sweeps,
+        channel_info,
+        spectrum,
+    })
+}
@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::spm_image::flip_img_data;
+use crate::spm_image::SpmImage;
+
+/// Nanonis scan (`.sxm`), a text header followed by big-endian float32 frames
+#[derive(Debug)]
+pub struct SxmImage {
+    pub filepath: PathBuf,
+    pub img_id: String,
+    pub datetime: DateTime<Utc>,
+    /// Size in pixels
+    pub xres: usize,
+    /// Size in pixels
+    pub yres: usize,
+    /// Size in nm
+    pub xsize: f64,
+    /// Size in nm
+    pub ysize: f64,
+    /// Offset in nm
+    pub xoffset: f64,
+    /// Offset in nm
+    pub yoffset: f64,
+    /// Rotation in degrees
+    pub scan_angle: f64,
+    pub scan_dir: SxmScanDirection,
+    /// Bias in V
+    pub bias: f64,
+    /// Acquisition time in s
+    pub acq_time: f64,
+    pub comment: String,
+    /// Parameters of the Z-controller, e.g. `Setpoint` -> `1.000E-10 A`
+    pub z_controller: BTreeMap<String, String>,
+    pub data_info: Vec<SxmChannelInfo>,
+    /// All sections of the header, with the lines of multi-line values joined by `\n`
+    pub header: BTreeMap<String, String>,
+    /// Forward and, if recorded, backward image of each channel
+    pub channels: Vec<SpmImage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SxmScanDirection {
+    /// First line at the bottom of the image
+    Up,
+    /// First line at the top of the image
+    Down,
+}
+
+impl SxmScanDirection {
+    fn from_str(s: &str) -> Self {
+        match s {
+            "up" => Self::Up,
+            _ => Self::Down,
+        }
+    }
+}
+
+/// Row of the `DATA_INFO` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct SxmChannelInfo {
+    pub channel: i32,
+    pub name: String,
+    pub unit: String,
+    /// `forward`, `backward` or `both`
+    pub direction: String,
+    pub calibration: f64,
+    pub offset: f64,
+}
+
+impl SxmChannelInfo {
+    fn directions(&self) -> Vec<&str> {
+        match self.direction.as_str() {
+            "both" => vec!["fwd", "bwd"],
+            "backward" => vec!["bwd"],
+            _ => vec!["fwd"],
+        }
+    }
+}
+
+// End of the header, the data starts right after
+const DATA_MARKER: &[u8] = b"\x1a\x04";
+
+/// Splits the header into its `:NAME:` sections
+fn parse_sxm_header(header: &str) -> BTreeMap<String, String> {
+    let mut sections = BTreeMap::new();
+    let mut name: Option<&str> = None;
+    let mut lines: Vec<&str> = Vec::new();
+
+    for line in header.lines() {
+        let trimmed = line.trim_end();
+        if trimmed.len() > 1 && trimmed.starts_with(':') && trimmed.ends_with(':') {
+            if let Some(name) = name {
+                sections.insert(name.to_string(), lines.join("\n").trim().to_string());
+            }
+            name = Some(&trimmed[1..trimmed.len() - 1]);
+            lines.clear();
+        } else {
+            lines.push(trimmed);
+        }
+    }
+    if let Some(name) = name {
+        sections.insert(name.to_string(), lines.join("\n").trim().to_string());
+    }
+    sections
+}
+
+/// Tab separated table with the column names in the first row
+fn parse_sxm_table(section: &str) -> Vec<BTreeMap<String, String>> {
+    let mut rows = section
+        .lines()
+        .map(|line| line.trim().split('\t').map(str::trim).collect::<Vec<_>>());
+    let columns = match rows.next() {
+        Some(columns) => columns,
+        None => return Vec::new(),
+    };
+    rows.map(|row| {
+        columns
+            .iter()
+            .zip(row)
+            .map(|(column, value)| (column.to_string(), value.to_string()))
+            .collect()
+    })
+    .collect()
+}
+
+fn parse_data_info(section: &str) -> Result<Vec<SxmChannelInfo>> {
+    parse_sxm_table(section)
+        .into_iter()
+        .map(|row| {
+            let get = |column: &str| {
+                row.get(column)
+                    .ok_or_else(|| anyhow!("DATA_INFO without column {}", column))
+            };
+            Ok(SxmChannelInfo {
+                channel: get("Channel")?.parse()?,
+                name: get("Name")?.to_string(),
+                unit: get("Unit")?.to_string(),
+                direction: get("Direction")?.to_string(),
+                calibration: get("Calibration")?.parse()?,
+                offset: get("Offset")?.parse()?,
+            })
+        })
+        .collect()
+}
+
+/// Whitespace separated numbers of a section, e.g. `SCAN_RANGE`
+fn parse_numbers(header: &BTreeMap<String, String>, name: &str) -> Result<Vec<f64>> {
+    header
+        .get(name)
+        .ok_or_else(|| anyhow!("Missing {} in header", name))?
+        .split_whitespace()
+        .map(|x| Ok(x.parse::<f64>()?))
+        .collect()
+}
+
+fn parse_pair(header: &BTreeMap<String, String>, name: &str) -> Result<(f64, f64)> {
+    match parse_numbers(header, name)?[..] {
+        [x, y] => Ok((x, y)),
+        _ => Err(anyhow!("Expected two values for {}", name)),
+    }
+}
+
+pub(crate) fn parse_datetime(date: &str, time: &str) -> Result<DateTime<Utc>> {
+    let datetime =
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%d.%m.%Y %H:%M:%S")?;
+    Ok(datetime.and_utc())
+}
+
+pub fn read_sxm(filename: &str) -> Result<SxmImage> {
+    let bytes = fs::read(filename)?;
+
+    let header_end = bytes
+        .windows(DATA_MARKER.len())
+        .position(|window| window == DATA_MARKER)
+        .ok_or_else(|| anyhow!("No data marker found in {}", filename))?;
+    let header = parse_sxm_header(&String::from_utf8_lossy(&bytes[..header_end]));
+    let data = &bytes[header_end + DATA_MARKER.len()..];
+
+    let get = |name: &str| header.get(name).map(String::as_str).unwrap_or_default();
+
+    let (xres, yres) = parse_pair(&header, "SCAN_PIXELS")?;
+    let (xres, yres) = (xres as usize, yres as usize);
+    let (xsize, ysize) = parse_pair(&header, "SCAN_RANGE")?;
+    let (xoffset, yoffset) = parse_pair(&header, "SCAN_OFFSET")?;
+    let scan_angle = get("SCAN_ANGLE").parse().unwrap_or_default();
+    let scan_dir = SxmScanDirection::from_str(get("SCAN_DIR"));
+    let bias = get("BIAS").parse().unwrap_or_default();
+    let acq_time = get("ACQ_TIME").parse().unwrap_or_default();
+    let datetime = parse_datetime(get("REC_DATE"), get("REC_TIME"))?;
+
+    let z_controller = parse_sxm_table(get("Z-CONTROLLER"))
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    let data_info = parse_data_info(get("DATA_INFO"))?;
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+    let img_id = basename.to_string();
+
+    let frame_len = xres * yres;
+    let num_frames: usize = data_info.iter().map(|info| info.directions().len()).sum();
+    if frame_len == 0 || data.len() < num_frames * frame_len * 4 {
+        return Err(anyhow!(
+            "Expected {} frames of {}x{} pixels, file has {} bytes of data",
+            num_frames,
+            xres,
+            yres,
+            data.len()
+        ));
+    }
+
+    let mut frames = data.chunks_exact(frame_len * 4).map(|frame| {
+        frame
+            .chunks_exact(4)
+            .map(|x| f64::from(f32::from_be_bytes([x[0], x[1], x[2], x[3]])))
+            .collect::<Vec<f64>>()
+    });
+
+    let mut channels = Vec::with_capacity(num_frames);
+    for info in data_info.iter() {
+        for direction in info.directions() {
+            let mut img_data = frames.next().unwrap();
+            // Backward lines are stored in the order they were scanned, right to left
+            if direction == "bwd" {
+                img_data
+                    .chunks_exact_mut(xres)
+                    .for_each(|line| line.reverse());
+            }
+            if scan_dir == SxmScanDirection::Up {
+                img_data = flip_img_data(img_data, xres as u32, yres as u32);
+            }
+            channels.push(SpmImage {
+                img_id: format!("{}_{}_{}", basename, info.name, direction),
+                xres,
+                yres,
+                xsize: xsize * 1e9,
+                ysize: ysize * 1e9,
+                z_unit: info.unit.clone(),
+                img_data,
+            });
+        }
+    }
+
+    Ok(SxmImage {
+        filepath,
+        img_id,
+        datetime,
+        xres,
+        yres,
+        xsize: xsize * 1e9,
+        ysize: ysize * 1e9,
+        xoffset: xoffset * 1e9,
+        yoffset: yoffset * 1e9,
+        scan_angle,
+        scan_dir,
+        bias,
+        acq_time,
+        comment: get("COMMENT").to_string(),
+        z_controller,
+        data_info,
+        header,
+        channels,
+    })
+}
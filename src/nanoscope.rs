@@ -0,0 +1,310 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::spm_image::flip_img_data;
+use crate::spm_image::SpmImage;
+use crate::utils::length_to_nm;
+
+/// Bruker / Veeco NanoScope file (`.spm`, `.000`), a text header of `\Key: value` lines
+/// followed by integer image data
+#[derive(Debug)]
+pub struct NanoscopeFile {
+    pub filepath: PathBuf,
+    /// E.g. `0x09200000`
+    pub version: String,
+    pub datetime: Option<DateTime<Utc>>,
+    /// Sections of the header in the order of the file, e.g. `Ciao scan list`
+    pub header: Vec<NanoscopeSection>,
+    pub channels: Vec<NanoscopeChannel>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NanoscopeSection {
+    pub name: String,
+    pub params: BTreeMap<String, String>,
+}
+
+/// Image of one `Ciao image list` section
+#[derive(Debug)]
+pub struct NanoscopeChannel {
+    /// E.g. `Height Sensor`
+    pub name: String,
+    /// `Trace` or `Retrace`
+    pub line_direction: String,
+    /// Factor from the raw integers to `z_unit`
+    pub z_scale: f64,
+    pub params: BTreeMap<String, String>,
+    pub img_data: SpmImage,
+}
+
+impl NanoscopeFile {
+    /// Parameters of the first section with the given name
+    pub fn section(&self, name: &str) -> Option<&BTreeMap<String, String>> {
+        self.header
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| &s.params)
+    }
+
+    pub fn channel(&self, name: &str) -> Option<&NanoscopeChannel> {
+        self.channels.iter().find(|c| c.name == name)
+    }
+}
+
+const FILE_LIST: &str = "File list";
+const FILE_LIST_END: &str = "File list end";
+const IMAGE_LIST: &str = "Ciao image list";
+const SCAN_LIST: &str = "Ciao scan list";
+
+/// The header is Latin-1 encoded, e.g. for `µ`
+fn parse_nanoscope_header(bytes: &[u8]) -> Vec<NanoscopeSection> {
+    let text: String = bytes.iter().map(|&b| char::from(b)).collect();
+    let mut sections: Vec<NanoscopeSection> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        let Some(line) = line.strip_prefix('\\') else {
+            continue;
+        };
+        if let Some(name) = line.strip_prefix('*') {
+            sections.push(NanoscopeSection {
+                name: name.to_string(),
+                params: BTreeMap::new(),
+            });
+        } else if let Some(section) = sections.last_mut() {
+            // Soft-scale keys contain a colon, e.g. `@2:Z scale`
+            let (key, value) = line
+                .split_once(": ")
+                .unwrap_or((line.trim_end_matches(':'), ""));
+            section
+                .params
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    sections
+}
+
+/// First number of a value like `2.00000 1.50000 ~m` or `512`
+fn first_number(value: &str) -> Option<f64> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// Sizes in nm from a value like `2.00000 1.50000 ~m` (`~m` is µm) or `500 nm`, `None`
+/// for values that are not sizes
+fn parse_scan_size(value: &str) -> Result<Option<(f64, Option<f64>)>> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let (numbers, unit) = match parts[..] {
+        [x, y, unit] => (vec![x, y], unit),
+        [x, unit] => (vec![x], unit),
+        _ => return Ok(None),
+    };
+    let numbers: Option<Vec<f64>> = numbers.iter().map(|x| x.parse().ok()).collect();
+    let Some(numbers) = numbers else {
+        return Ok(None);
+    };
+    let factor = length_to_nm(&unit.replace('~', "\u{b5}"))
+        .ok_or_else(|| anyhow!("Unknown unit {} of the scan size", unit))?;
+    Ok(Some((
+        numbers[0] * factor,
+        numbers.get(1).map(|y| y * factor),
+    )))
+}
+
+/// Channel name of `S [Height] "Height Sensor"`, or of `Height` in old versions
+fn image_data_name(value: &str) -> String {
+    if let Some((_, rest)) = value.split_once('"') {
+        return rest.trim_end_matches('"').to_string();
+    }
+    match value.split_once('[') {
+        Some((_, rest)) => rest.split(']').next().unwrap_or_default().to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Value and unit of a sensitivity like `V 20.00000 nm/V`
+fn parse_sensitivity(value: &str) -> Option<(f64, String)> {
+    let mut parts = value.split_whitespace().skip(1);
+    let number = parts.next()?.parse().ok()?;
+    Some((number, parts.next().unwrap_or_default().to_string()))
+}
+
+/// Resolves a soft-scale value like `V [Sens. ZsensSens] (0.000375 V/LSB) 24.576 V` to
+/// the factor and unit per LSB of 16 bit data
+///
+/// The hard scale in parentheses is in units per LSB, without it the full scale
+/// value spans the 16 bit range. The sensitivity in brackets, looked up as
+/// `@Sens. ZsensSens` in the header, converts it to physical units.
+fn resolve_z_scale(value: &str, sections: &[NanoscopeSection]) -> Result<(f64, String)> {
+    let (hard_scale, hard_unit) = match value.split_once('(') {
+        Some((_, rest)) => {
+            let inner = rest
+                .split(')')
+                .next()
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect::<Vec<_>>();
+            let scale: f64 = inner
+                .first()
+                .ok_or_else(|| anyhow!("No hard scale in `{}`", value))?
+                .parse()?;
+            let unit = inner
+                .get(1)
+                .map(|u| u.trim_end_matches("/LSB"))
+                .unwrap_or("V");
+            (scale, unit.to_string())
+        }
+        None => {
+            // `V [Sens. Zscan] 26.3 V` or `V 26.3 V`
+            let full_scale = match value.split_once(']') {
+                Some((_, rest)) => rest,
+                None => value
+                    .split_once(' ')
+                    .map(|(_, rest)| rest)
+                    .unwrap_or_default(),
+            };
+            let mut parts = full_scale.split_whitespace();
+            let scale: f64 = parts
+                .next()
+                .ok_or_else(|| anyhow!("Invalid Z scale `{}`", value))?
+                .parse()?;
+            (scale / 65536.0, parts.next().unwrap_or("V").to_string())
+        }
+    };
+
+    let soft_key = match value.split_once('[') {
+        Some((_, rest)) => format!("@{}", rest.split(']').next().unwrap_or_default()),
+        None => return Ok((hard_scale, hard_unit)),
+    };
+    let sensitivity = sections
+        .iter()
+        .find_map(|s| s.params.get(&soft_key))
+        .and_then(|v| parse_sensitivity(v));
+    match sensitivity {
+        Some((factor, unit)) => match unit.strip_suffix(&format!("/{}", hard_unit)) {
+            Some(unit) => Ok((hard_scale * factor, unit.to_string())),
+            None => Ok((hard_scale, hard_unit)),
+        },
+        None => Ok((hard_scale, hard_unit)),
+    }
+}
+
+pub fn read_nanoscope(filename: &str) -> Result<NanoscopeFile> {
+    let bytes = fs::read(filename)?;
+    if !bytes.starts_with(format!("\\*{}", FILE_LIST).as_bytes()) {
+        return Err(anyhow!("Not a NanoScope file: {}", filename));
+    }
+    let end_marker = format!("\\*{}", FILE_LIST_END);
+    let header_end = bytes
+        .windows(end_marker.len())
+        .position(|window| window == end_marker.as_bytes())
+        .ok_or_else(|| anyhow!("No end of header found in {}", filename))?;
+    let header = parse_nanoscope_header(&bytes[..header_end]);
+
+    let section = |name: &str| header.iter().find(|s| s.name == name).map(|s| &s.params);
+    let file_list = section(FILE_LIST).unwrap();
+    let version = file_list.get("Version").cloned().unwrap_or_default();
+    let datetime = file_list
+        .get("Date")
+        .and_then(|d| NaiveDateTime::parse_from_str(d, "%I:%M:%S %p %a %b %d %Y").ok())
+        .map(|d| d.and_utc());
+    let scan_size = section(SCAN_LIST)
+        .and_then(|s| s.get("Scan Size").or_else(|| s.get("Scan size")))
+        .map(|v| parse_scan_size(v))
+        .transpose()?
+        .flatten();
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+
+    let mut channels = Vec::new();
+    for params in header
+        .iter()
+        .filter(|s| s.name == IMAGE_LIST)
+        .map(|s| &s.params)
+    {
+        let get = |key: &str| params.get(key).map(String::as_str).unwrap_or_default();
+        let get_usize = |key: &str| first_number(get(key)).map(|v| v as usize);
+
+        let xres = get_usize("Samps/line").ok_or_else(|| anyhow!("No Samps/line"))?;
+        let yres = get_usize("Number of lines").ok_or_else(|| anyhow!("No Number of lines"))?;
+        let offset = get_usize("Data offset").ok_or_else(|| anyhow!("No Data offset"))?;
+        let num_pixels = xres * yres;
+        let bytes_per_pixel = get_usize("Bytes/pixel")
+            .or_else(|| get_usize("Data length").map(|len| len / num_pixels.max(1)))
+            .unwrap_or(2);
+        if bytes_per_pixel != 2 && bytes_per_pixel != 4 {
+            return Err(anyhow!("Unsupported {} bytes per pixel", bytes_per_pixel));
+        }
+        if bytes.len() < offset + num_pixels * bytes_per_pixel {
+            return Err(anyhow!(
+                "Expected {}x{} pixels at offset {}, file has {} bytes",
+                xres,
+                yres,
+                offset,
+                bytes.len()
+            ));
+        }
+
+        let name = match params.get("@2:Image Data") {
+            Some(value) => image_data_name(value),
+            None => get("Image data").to_string(),
+        };
+        let line_direction = get("Line Direction").to_string();
+        let (mut z_scale, z_unit) = resolve_z_scale(get("@2:Z scale"), &header)?;
+        // The scale refers to 16 bit data, 32 bit data has 65536 times finer steps
+        if bytes_per_pixel == 4 {
+            z_scale /= 65536.0;
+        }
+
+        let data = &bytes[offset..offset + num_pixels * bytes_per_pixel];
+        let raw: Vec<f64> = match bytes_per_pixel {
+            4 => data
+                .chunks_exact(4)
+                .map(|x| f64::from(i32::from_le_bytes(x.try_into().unwrap())) * z_scale)
+                .collect(),
+            _ => data
+                .chunks_exact(2)
+                .map(|x| f64::from(i16::from_le_bytes(x.try_into().unwrap())) * z_scale)
+                .collect(),
+        };
+        // The first line in the file is the bottom of the image
+        let img_data = flip_img_data(raw, xres as u32, yres as u32);
+
+        let (xsize, ysize) = match parse_scan_size(get("Scan Size"))?.or(scan_size) {
+            Some((xsize, Some(ysize))) => (xsize, ysize),
+            Some((xsize, None)) => (xsize, xsize * yres as f64 / xres as f64),
+            None => (0.0, 0.0),
+        };
+        let direction = if line_direction == "Retrace" {
+            "bwd"
+        } else {
+            "fwd"
+        };
+
+        channels.push(NanoscopeChannel {
+            img_data: SpmImage {
+                img_id: format!("{}_{}_{}", basename, name, direction),
+                xres,
+                yres,
+                xsize,
+                ysize,
+                z_unit,
+                img_data,
+            },
+            name,
+            line_direction,
+            z_scale,
+            params: params.clone(),
+        });
+    }
+
+    Ok(NanoscopeFile {
+        filepath,
+        version,
+        datetime,
+        header,
+        channels,
+    })
+}
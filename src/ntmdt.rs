@@ -0,0 +1,312 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::spm_image::flip_img_data;
+use crate::spm_image::SpmImage;
+use crate::spm_spectrum::{SpectrumChannel, SpmSpectrum};
+use crate::utils::{length_to_nm, Bytereading};
+
+/// NT-MDT file (`.mdt`), a sequence of frames with little-endian binary headers
+#[derive(Debug)]
+pub struct MdtFile {
+    pub filepath: PathBuf,
+    pub frames: Vec<MdtFrame>,
+}
+
+impl MdtFile {
+    pub fn images(&self) -> impl Iterator<Item = &SpmImage> {
+        self.frames.iter().filter_map(|f| match &f.data {
+            MdtData::Image(img) => Some(img),
+            _ => None,
+        })
+    }
+
+    pub fn spectra(&self) -> impl Iterator<Item = &SpmSpectrum> {
+        self.frames.iter().filter_map(|f| match &f.data {
+            MdtData::Spectrum(spectrum) => Some(spectrum),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MdtFrame {
+    pub index: usize,
+    /// E.g. 0 for scanned, 1 for spectroscopy, 3 for text and 106 for MDA frames
+    pub frame_type: u16,
+    pub datetime: Option<DateTime<Utc>>,
+    /// Parameters of scanned and spectroscopy frames
+    pub params: Option<MdtScanParams>,
+    pub data: MdtData,
+}
+
+#[derive(Debug)]
+pub enum MdtData {
+    Image(SpmImage),
+    Spectrum(SpmSpectrum),
+    /// Text, MDA, palette and point curve frames
+    Unsupported,
+}
+
+/// Axis of a frame, `value = offset + step * raw`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MdtAxisScale {
+    pub offset: f64,
+    pub step: f64,
+    pub unit: String,
+}
+
+/// Scan variables of a scanned or spectroscopy frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct MdtScanParams {
+    pub x_scale: MdtAxisScale,
+    pub y_scale: MdtAxisScale,
+    pub z_scale: MdtAxisScale,
+    pub channel_index: u16,
+    pub mode: u16,
+    pub adc_index: u8,
+    pub input_signal: u8,
+    pub scan_dir: u8,
+    pub velocity: f64,
+    pub setpoint: f64,
+    /// Bias in V
+    pub bias: f64,
+}
+
+const MDT_MAGIC: [u8; 4] = [0x01, 0xb0, 0x04, 0xad];
+const FILE_HEADER_SIZE: usize = 33;
+const FRAME_HEADER_SIZE: usize = 22;
+// Axis scales and scan variables up to the non-linearity correction
+const SCAN_VARS_SIZE: usize = 75;
+// Mode, resolution and number of dots after the scan variables
+const FRAME_MODE_SIZE: usize = 8;
+
+const FRAME_SCANNED: u16 = 0;
+const FRAME_SPECTROSCOPY: u16 = 1;
+
+fn mdt_unit(code: i16) -> &'static str {
+    match code {
+        -5 => "m",
+        -4 => "cm",
+        -3 => "mm",
+        -2 => "\u{b5}m",
+        -1 => "nm",
+        0 => "\u{c5}",
+        1 => "nA",
+        2 => "V",
+        4 => "kHz",
+        5 => "deg",
+        6 => "%",
+        7 => "\u{b0}C",
+        8 => "V",
+        9 => "s",
+        10 => "ms",
+        11 => "\u{b5}s",
+        12 => "ns",
+        13 => "counts",
+        14 => "px",
+        _ => "",
+    }
+}
+
+fn read_axis_scale(cursor: &mut Cursor<&[u8]>) -> MdtAxisScale {
+    let offset = f64::from(cursor.read_f32_le());
+    let step = f64::from(cursor.read_f32_le());
+    let unit = mdt_unit(cursor.read_i16_le()).to_string();
+    MdtAxisScale { offset, step, unit }
+}
+
+fn read_scan_params(cursor: &mut Cursor<&[u8]>) -> MdtScanParams {
+    let x_scale = read_axis_scale(cursor);
+    let y_scale = read_axis_scale(cursor);
+    let z_scale = read_axis_scale(cursor);
+    let channel_index = cursor.read_u16_le();
+    let mode = cursor.read_u16_le();
+    // Resolution, number of acquisitions, step length and ADC time
+    cursor.skip(12);
+    let _adc_gain = cursor.read_u8_le();
+    let adc_index = cursor.read_u8_le();
+    let input_signal = cursor.read_u8_le();
+    let _substract_plane = cursor.read_u8_le();
+    let scan_dir = cursor.read_u8_le();
+    let _power_of_2 = cursor.read_u8_le();
+    let velocity = f64::from(cursor.read_f32_le());
+    let setpoint = f64::from(cursor.read_f32_le());
+    let bias = f64::from(cursor.read_f32_le());
+    MdtScanParams {
+        x_scale,
+        y_scale,
+        z_scale,
+        channel_index,
+        mode,
+        adc_index,
+        input_signal,
+        scan_dir,
+        velocity,
+        setpoint,
+        bias,
+    }
+}
+
+/// Raw int16 data of a scanned or spectroscopy frame, skipping the dots measured
+/// during the scan
+fn read_frame_data(frame: &[u8], var_size: usize) -> Result<(usize, usize, Vec<i16>)> {
+    let start = FRAME_HEADER_SIZE + var_size;
+    if frame.len() < start + FRAME_MODE_SIZE {
+        return Err(anyhow!("Frame of {} bytes without frame mode", frame.len()));
+    }
+    let mut cursor = Cursor::new(&frame[start..]);
+    let _mode = cursor.read_u16_le();
+    let xres = cursor.read_u16_le() as usize;
+    let yres = cursor.read_u16_le() as usize;
+    let num_dots = cursor.read_u16_le() as usize;
+
+    let mut pos = start + FRAME_MODE_SIZE;
+    if num_dots > 0 {
+        // Dots header, then coordinates and forward and backward sizes of each dot
+        let dots_size = 6 + num_dots * 16;
+        if frame.len() < pos + dots_size {
+            return Err(anyhow!("Frame too short for {} dots", num_dots));
+        }
+        let mut cursor = Cursor::new(&frame[pos + 6..pos + dots_size]);
+        let mut num_values = 0;
+        for _ in 0..num_dots {
+            cursor.skip(8);
+            num_values += cursor.read_i32_le().max(0) as usize;
+            num_values += cursor.read_i32_le().max(0) as usize;
+        }
+        pos += dots_size + num_values * 2;
+    }
+
+    let num_pixels = xres * yres;
+    if frame.len() < pos + num_pixels * 2 {
+        return Err(anyhow!(
+            "Expected {}x{} values, frame has {} bytes",
+            xres,
+            yres,
+            frame.len()
+        ));
+    }
+    let data = frame[pos..pos + num_pixels * 2]
+        .chunks_exact(2)
+        .map(|x| i16::from_le_bytes([x[0], x[1]]))
+        .collect();
+    Ok((xres, yres, data))
+}
+
+fn read_frame(frame: &[u8], index: usize, basename: &str) -> Result<MdtFrame> {
+    let mut cursor = Cursor::new(frame);
+    let _size = cursor.read_u32_le();
+    let frame_type = cursor.read_u16_le();
+    let _version = cursor.read_u16_le();
+    let date: Vec<u16> = (0..6).map(|_| cursor.read_u16_le()).collect();
+    let var_size = cursor.read_u16_le() as usize;
+    let datetime = NaiveDate::from_ymd_opt(date[0].into(), date[1].into(), date[2].into())
+        .and_then(|d| d.and_hms_opt(date[3].into(), date[4].into(), date[5].into()))
+        .map(|d| d.and_utc());
+
+    if frame_type != FRAME_SCANNED && frame_type != FRAME_SPECTROSCOPY {
+        return Ok(MdtFrame {
+            index,
+            frame_type,
+            datetime,
+            params: None,
+            data: MdtData::Unsupported,
+        });
+    }
+    if var_size < SCAN_VARS_SIZE || frame.len() < FRAME_HEADER_SIZE + var_size {
+        return Err(anyhow!(
+            "Frame {} has {} bytes of variables",
+            index,
+            var_size
+        ));
+    }
+    let params = read_scan_params(&mut cursor);
+    let (xres, yres, raw) = read_frame_data(frame, var_size)?;
+
+    let z = &params.z_scale;
+    let values: Vec<f64> = raw
+        .iter()
+        .map(|&v| z.offset + z.step * f64::from(v))
+        .collect();
+
+    let data = if frame_type == FRAME_SCANNED {
+        let factor = |unit: &str| {
+            length_to_nm(unit)
+                .ok_or_else(|| anyhow!("Unknown length unit {} of frame {}", unit, index))
+        };
+        let x_factor = factor(&params.x_scale.unit)?;
+        let y_factor = factor(&params.y_scale.unit)?;
+        MdtData::Image(SpmImage {
+            img_id: format!("{}_{}", basename, index),
+            xres,
+            yres,
+            xsize: xres as f64 * params.x_scale.step * x_factor,
+            ysize: yres as f64 * params.y_scale.step * y_factor,
+            z_unit: z.unit.clone(),
+            // The first line in the file is the bottom of the image
+            img_data: flip_img_data(values, xres as u32, yres as u32),
+        })
+    } else {
+        // Curves are a single line along the x axis
+        let x = &params.x_scale;
+        MdtData::Spectrum(SpmSpectrum {
+            spec_id: format!("{}_{}", basename, index),
+            x_label: "X".to_string(),
+            x_unit: x.unit.clone(),
+            x_data: (0..values.len())
+                .map(|i| x.offset + x.step * i as f64)
+                .collect(),
+            channels: vec![SpectrumChannel {
+                name: "Z".to_string(),
+                unit: z.unit.clone(),
+                data: values,
+            }],
+            xpos: 0.0,
+            ypos: 0.0,
+        })
+    };
+
+    Ok(MdtFrame {
+        index,
+        frame_type,
+        datetime,
+        params: Some(params),
+        data,
+    })
+}
+
+pub fn read_mdt(filename: &str) -> Result<MdtFile> {
+    let bytes = fs::read(filename)?;
+    if bytes.len() < FILE_HEADER_SIZE || bytes[..4] != MDT_MAGIC {
+        return Err(anyhow!("Not an NT-MDT file: {}", filename));
+    }
+    let num_frames = usize::from(u16::from_le_bytes([bytes[12], bytes[13]])) + 1;
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+
+    let mut frames = Vec::with_capacity(num_frames);
+    let mut pos = FILE_HEADER_SIZE;
+    for index in 0..num_frames {
+        if bytes.len() < pos + FRAME_HEADER_SIZE {
+            return Err(anyhow!("Frame {} starts beyond the end of the file", index));
+        }
+        let size = u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+        if size < FRAME_HEADER_SIZE || bytes.len() < pos + size {
+            return Err(anyhow!(
+                "Frame {} has an invalid size of {} bytes",
+                index,
+                size
+            ));
+        }
+        frames.push(read_frame(&bytes[pos..pos + size], index, basename)?);
+        pos += size;
+    }
+
+    Ok(MdtFile { filepath, frames })
+}
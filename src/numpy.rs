@@ -0,0 +1,509 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use flate2::Crc;
+use ndarray::Array3;
+
+use crate::mulfile::MulImage;
+use crate::nanonis_3ds::Nanonis3ds;
+use crate::nanonis_dat::NanonisDat;
+use crate::nanonis_sxm::SxmImage;
+use crate::spm_image::SpmImage;
+use crate::spm_spectrum::SpmSpectrum;
+use crate::utils::Bytewriting;
+
+/// Array in C order, written as NumPy `.npy`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    pub data: NpyData,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NpyData {
+    /// dtype `<f8`
+    Float64(Vec<f64>),
+    /// dtype `<U{n}` with `n` the longest string in characters
+    Unicode(Vec<String>),
+}
+
+impl NpyArray {
+    pub fn float64(shape: &[usize], data: Vec<f64>) -> Self {
+        Self {
+            shape: shape.to_vec(),
+            data: NpyData::Float64(data),
+        }
+    }
+
+    /// 1D array of the values
+    pub fn vector(data: Vec<f64>) -> Self {
+        Self::float64(&[data.len()], data)
+    }
+
+    /// 0D array of a value, e.g. the bias
+    pub fn scalar(value: f64) -> Self {
+        Self::float64(&[], vec![value])
+    }
+
+    /// 0D array of a string, e.g. a unit
+    pub fn string(s: &str) -> Self {
+        Self {
+            shape: Vec::new(),
+            data: NpyData::Unicode(vec![s.to_string()]),
+        }
+    }
+
+    /// 1D array of strings, e.g. channel names
+    pub fn strings(data: Vec<String>) -> Self {
+        Self {
+            shape: vec![data.len()],
+            data: NpyData::Unicode(data),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match &self.data {
+            NpyData::Float64(data) => data.len(),
+            NpyData::Unicode(data) => data.len(),
+        }
+    }
+
+    fn descr(&self) -> String {
+        match &self.data {
+            NpyData::Float64(_) => "<f8".to_string(),
+            NpyData::Unicode(data) => {
+                let len = data.iter().map(|s| s.chars().count()).max().unwrap_or(0);
+                format!("<U{}", len.max(1))
+            }
+        }
+    }
+
+    /// Format version 1.0, a Python dict literal as header padded to 64 bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.shape.iter().product::<usize>() != self.len() {
+            return Err(anyhow!(
+                "Shape {:?} does not match {} values",
+                self.shape,
+                self.len()
+            ));
+        }
+        let shape = match self.shape.len() {
+            1 => format!("({},)", self.shape[0]),
+            _ => format!(
+                "({})",
+                self.shape
+                    .iter()
+                    .map(usize::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        let mut header = format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+            self.descr(),
+            shape
+        );
+        // Magic, version and header length take 10 bytes, the header ends with `\n`
+        let padding = 63 - (NPY_MAGIC.len() + 4 + header.len()) % 64;
+        header.push_str(&" ".repeat(padding));
+        header.push('\n');
+
+        let mut buffer = NPY_MAGIC.to_vec();
+        buffer.extend_from_slice(&[1, 0]);
+        buffer.write_u16_le(header.len() as u16);
+        buffer.extend_from_slice(header.as_bytes());
+        match &self.data {
+            NpyData::Float64(data) => {
+                for x in data {
+                    buffer.write_f64_le(*x);
+                }
+            }
+            NpyData::Unicode(data) => {
+                let len = self.descr()[2..].parse::<usize>()?;
+                for s in data {
+                    let mut chars: Vec<u32> = s.chars().map(u32::from).collect();
+                    chars.resize(len, 0);
+                    for c in chars {
+                        buffer.write_u32_le(c);
+                    }
+                }
+            }
+        }
+        Ok(buffer)
+    }
+}
+
+impl From<&SpmImage> for NpyArray {
+    /// Shape (yres, xres), first row at the top of the image
+    fn from(image: &SpmImage) -> Self {
+        Self::float64(&[image.yres, image.xres], image.img_data.clone())
+    }
+}
+
+impl From<&SpmSpectrum> for NpyArray {
+    /// Shape (1 + number of channels, number of points), the x axis first
+    fn from(spectrum: &SpmSpectrum) -> Self {
+        let mut data = spectrum.x_data.clone();
+        for channel in spectrum.channels.iter() {
+            data.extend_from_slice(&channel.data);
+        }
+        Self::float64(&[1 + spectrum.channels.len(), spectrum.x_data.len()], data)
+    }
+}
+
+impl From<&Array3<f64>> for NpyArray {
+    fn from(volume: &Array3<f64>) -> Self {
+        Self::float64(volume.shape(), volume.iter().copied().collect())
+    }
+}
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+pub fn write_npy(filename: &str, array: &NpyArray) -> Result<()> {
+    fs::write(filename, array.to_bytes()?)?;
+    Ok(())
+}
+
+/// Pixel positions in nm from 0, the y axis from the top line
+fn pixel_axis(res: usize, size: f64) -> Vec<f64> {
+    (0..res).map(|i| i as f64 * size / res as f64).collect()
+}
+
+/// Data as `{img_id}`, the axes in nm as `{img_id}_x` and `{img_id}_y` and the unit
+/// as `{img_id}_z_unit`
+pub fn image_arrays(image: &SpmImage) -> Vec<(String, NpyArray)> {
+    let id = &image.img_id;
+    vec![
+        (id.clone(), NpyArray::from(image)),
+        (
+            format!("{}_x", id),
+            NpyArray::vector(pixel_axis(image.xres, image.xsize)),
+        ),
+        (
+            format!("{}_y", id),
+            NpyArray::vector(pixel_axis(image.yres, image.ysize)),
+        ),
+        (format!("{}_z_unit", id), NpyArray::string(&image.z_unit)),
+    ]
+}
+
+/// The x axis as `{spec_id}_x`, each channel as `{spec_id}_{name}` and the units and
+/// channel names as string arrays
+pub fn spectrum_arrays(spectrum: &SpmSpectrum) -> Vec<(String, NpyArray)> {
+    let id = &spectrum.spec_id;
+    let mut arrays = vec![
+        (
+            format!("{}_x", id),
+            NpyArray::vector(spectrum.x_data.clone()),
+        ),
+        (format!("{}_x_unit", id), NpyArray::string(&spectrum.x_unit)),
+    ];
+    for channel in spectrum.channels.iter() {
+        arrays.push((
+            format!("{}_{}", id, channel.name),
+            NpyArray::vector(channel.data.clone()),
+        ));
+    }
+    let names: Vec<String> = spectrum.channels.iter().map(|c| c.name.clone()).collect();
+    let units: Vec<String> = spectrum.channels.iter().map(|c| c.unit.clone()).collect();
+    arrays.push((format!("{}_channels", id), NpyArray::strings(names)));
+    arrays.push((format!("{}_units", id), NpyArray::strings(units)));
+    arrays.push((
+        format!("{}_position", id),
+        NpyArray::vector(vec![spectrum.xpos, spectrum.ypos]),
+    ));
+    arrays
+}
+
+/// Each channel with shape (yres, xres, number of points) as its name, the sweep as
+/// `sweep`, the grid axes in nm as `x` and `y` and the parameters as `params`
+pub fn grid_arrays(grid: &Nanonis3ds) -> Vec<(String, NpyArray)> {
+    let mut arrays = vec![
+        ("sweep".to_string(), NpyArray::vector(grid.sweep.clone())),
+        ("sweep_unit".to_string(), NpyArray::string(&grid.sweep_unit)),
+        (
+            "x".to_string(),
+            NpyArray::vector(pixel_axis(grid.xres, grid.xsize)),
+        ),
+        (
+            "y".to_string(),
+            NpyArray::vector(pixel_axis(grid.yres, grid.ysize)),
+        ),
+        ("params".to_string(), NpyArray::from(&grid.params)),
+        (
+            "param_names".to_string(),
+            NpyArray::strings(grid.param_names.clone()),
+        ),
+    ];
+    for channel in grid.channels.iter() {
+        arrays.push((channel.name.clone(), NpyArray::from(&channel.data)));
+        arrays.push((
+            format!("{}_unit", channel.name),
+            NpyArray::string(&channel.unit),
+        ));
+    }
+    arrays
+}
+
+/// ISO 8601 without time zone, as parsed by `numpy.datetime64`
+fn datetime_string(datetime: &DateTime<Utc>) -> String {
+    datetime.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// Keys and values of a header as string arrays `header_keys` and `header_values`
+fn header_arrays(header: &BTreeMap<String, String>) -> Vec<(String, NpyArray)> {
+    vec![
+        (
+            "header_keys".to_string(),
+            NpyArray::strings(header.keys().cloned().collect()),
+        ),
+        (
+            "header_values".to_string(),
+            NpyArray::strings(header.values().cloned().collect()),
+        ),
+    ]
+}
+
+/// Files that can be exported as a whole to `.npz`
+pub trait NpzExport {
+    /// All channels with their axes, followed by the metadata of the file
+    fn npz_arrays(&self) -> Vec<(String, NpyArray)>;
+}
+
+/// Writes all channels and the metadata of a file to `.npz`
+pub fn save_npz<T: NpzExport + ?Sized>(filename: &str, file: &T) -> Result<()> {
+    write_npz(filename, &file.npz_arrays())
+}
+
+impl NpzExport for [MulImage] {
+    /// Each image and its point scans, with metadata prefixed by the `img_id`, bias
+    /// in mV, current in nA, offsets in nm and scan duration in s
+    fn npz_arrays(&self) -> Vec<(String, NpyArray)> {
+        let mut arrays = Vec::new();
+        for img in self {
+            arrays.extend(image_arrays(&img.img_data));
+            for point_scan in img.point_scans.iter() {
+                arrays.extend(spectrum_arrays(&point_scan.spectrum));
+            }
+            let id = &img.img_id;
+            let metadata = [
+                (
+                    "datetime",
+                    NpyArray::string(&datetime_string(&img.datetime)),
+                ),
+                ("bias", NpyArray::scalar(img.bias)),
+                ("current", NpyArray::scalar(img.current)),
+                ("xoffset", NpyArray::scalar(img.xoffset)),
+                ("yoffset", NpyArray::scalar(img.yoffset)),
+                ("speed", NpyArray::scalar(img.speed)),
+                ("mode", NpyArray::scalar(f64::from(img.mode))),
+                ("sample", NpyArray::string(img.sample.trim())),
+                ("title", NpyArray::string(img.title.trim())),
+            ];
+            arrays.extend(
+                metadata
+                    .into_iter()
+                    .map(|(key, array)| (format!("{}_{}", id, key), array)),
+            );
+        }
+        arrays
+    }
+}
+
+impl NpzExport for Vec<MulImage> {
+    fn npz_arrays(&self) -> Vec<(String, NpyArray)> {
+        self.as_slice().npz_arrays()
+    }
+}
+
+impl NpzExport for SxmImage {
+    /// Each channel, bias in V, offsets in nm, angle in degrees, acquisition time in
+    /// s, the Z-controller setpoint and the header
+    fn npz_arrays(&self) -> Vec<(String, NpyArray)> {
+        let mut arrays: Vec<(String, NpyArray)> =
+            self.channels.iter().flat_map(image_arrays).collect();
+        let metadata = [
+            (
+                "datetime",
+                NpyArray::string(&datetime_string(&self.datetime)),
+            ),
+            ("bias", NpyArray::scalar(self.bias)),
+            ("xoffset", NpyArray::scalar(self.xoffset)),
+            ("yoffset", NpyArray::scalar(self.yoffset)),
+            ("scan_angle", NpyArray::scalar(self.scan_angle)),
+            ("acq_time", NpyArray::scalar(self.acq_time)),
+            (
+                "setpoint",
+                NpyArray::string(
+                    self.z_controller
+                        .get("Setpoint")
+                        .map(String::as_str)
+                        .unwrap_or_default(),
+                ),
+            ),
+            ("comment", NpyArray::string(&self.comment)),
+        ];
+        arrays.extend(
+            metadata
+                .into_iter()
+                .map(|(key, array)| (key.to_string(), array)),
+        );
+        arrays.extend(header_arrays(&self.header));
+        arrays
+    }
+}
+
+impl NpzExport for NanonisDat {
+    /// The spectrum, the experiment, its date and the header
+    fn npz_arrays(&self) -> Vec<(String, NpyArray)> {
+        let mut arrays = spectrum_arrays(&self.spectrum);
+        arrays.push(("experiment".to_string(), NpyArray::string(&self.experiment)));
+        if let Some(datetime) = &self.datetime {
+            arrays.push((
+                "datetime".to_string(),
+                NpyArray::string(&datetime_string(datetime)),
+            ));
+        }
+        arrays.extend(header_arrays(&self.header));
+        arrays
+    }
+}
+
+impl NpzExport for Nanonis3ds {
+    /// The grid, its center in nm and angle in degrees, start and end time and the
+    /// header
+    fn npz_arrays(&self) -> Vec<(String, NpyArray)> {
+        let mut arrays = grid_arrays(self);
+        arrays.push(("experiment".to_string(), NpyArray::string(&self.experiment)));
+        for (key, time) in [
+            ("start_time", &self.start_time),
+            ("end_time", &self.end_time),
+        ] {
+            if let Some(time) = time {
+                arrays.push((key.to_string(), NpyArray::string(&datetime_string(time))));
+            }
+        }
+        arrays.push(("xcenter".to_string(), NpyArray::scalar(self.xcenter)));
+        arrays.push(("ycenter".to_string(), NpyArray::scalar(self.ycenter)));
+        arrays.push(("angle".to_string(), NpyArray::scalar(self.angle)));
+        arrays.push(("comment".to_string(), NpyArray::string(&self.comment)));
+        arrays.extend(header_arrays(&self.header));
+        arrays
+    }
+}
+
+/// Names of the entries, with path separators and control characters replaced and
+/// repeated names numbered from `_1`, so that no array replaces another when loaded
+pub fn npz_names(arrays: &[(String, NpyArray)]) -> Vec<String> {
+    let mut used = HashSet::new();
+    arrays
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| {
+            let mut name: String = name
+                .chars()
+                .map(|c| match c {
+                    '/' | '\\' | ':' => '_',
+                    c if c.is_control() => '_',
+                    c => c,
+                })
+                .collect();
+            if name.is_empty() {
+                name = format!("arr_{}", i);
+            }
+            let mut unique = name.clone();
+            let mut n = 1;
+            while !used.insert(unique.clone()) {
+                unique = format!("{}_{}", name, n);
+                n += 1;
+            }
+            unique
+        })
+        .collect()
+}
+
+/// Zip fields are 32 bits without ZIP64 records
+fn zip_u32(value: usize, what: &str) -> Result<u32> {
+    u32::try_from(value).map_err(|_| anyhow!("{} exceeds the 4 GiB limit of a zip file", what))
+}
+
+/// Uncompressed zip of `{name}.npy` files, as written by `numpy.savez`, with the names
+/// from `npz_names`. Archives that need ZIP64, with more than 65535 entries or over
+/// 4 GiB, are an error.
+pub fn write_npz(filename: &str, arrays: &[(String, NpyArray)]) -> Result<()> {
+    const LOCAL_HEADER: u32 = 0x0403_4b50;
+    const CENTRAL_HEADER: u32 = 0x0201_4b50;
+    const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+    const VERSION: u16 = 20;
+    const UTF8_NAME: u16 = 0x0800;
+    // 1980-01-01 00:00, the earliest MS-DOS date
+    const DOS_DATE: u16 = (1 << 5) | 1;
+
+    let num_entries = u16::try_from(arrays.len()).map_err(|_| {
+        anyhow!(
+            "{} arrays exceed the 65535 entries of a zip file",
+            arrays.len()
+        )
+    })?;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut central_directory: Vec<u8> = Vec::new();
+    for (name, (_, array)) in npz_names(arrays).iter().zip(arrays.iter()) {
+        let name = format!("{}.npy", name);
+        let flags = if name.is_ascii() { 0 } else { UTF8_NAME };
+        let data = array.to_bytes()?;
+        let mut crc = Crc::new();
+        crc.update(&data);
+        let size = zip_u32(data.len(), &name)?;
+        let offset = zip_u32(buffer.len(), &name)?;
+        let name_len =
+            u16::try_from(name.len()).map_err(|_| anyhow!("Zip entry name {} too long", name))?;
+
+        let entry = |header: &mut Vec<u8>| {
+            header.write_u16_le(VERSION);
+            header.write_u16_le(flags);
+            // Stored without compression
+            header.write_u16_le(0);
+            header.write_u16_le(0);
+            header.write_u16_le(DOS_DATE);
+            header.write_u32_le(crc.sum());
+            header.write_u32_le(size);
+            header.write_u32_le(size);
+            header.write_u16_le(name_len);
+            header.write_u16_le(0);
+        };
+
+        buffer.write_u32_le(LOCAL_HEADER);
+        entry(&mut buffer);
+        buffer.extend_from_slice(name.as_bytes());
+        buffer.extend_from_slice(&data);
+
+        central_directory.write_u32_le(CENTRAL_HEADER);
+        central_directory.write_u16_le(VERSION);
+        entry(&mut central_directory);
+        // Comment length, disk, internal and external attributes
+        central_directory.write_u16_le(0);
+        central_directory.write_u16_le(0);
+        central_directory.write_u16_le(0);
+        central_directory.write_u32_le(0);
+        central_directory.write_u32_le(offset);
+        central_directory.extend_from_slice(name.as_bytes());
+    }
+
+    let central_directory_offset = zip_u32(buffer.len(), "Central directory")?;
+    let central_directory_size = zip_u32(central_directory.len(), "Central directory")?;
+    buffer.extend_from_slice(&central_directory);
+    buffer.write_u32_le(END_OF_CENTRAL_DIRECTORY);
+    buffer.write_u16_le(0);
+    buffer.write_u16_le(0);
+    buffer.write_u16_le(num_entries);
+    buffer.write_u16_le(num_entries);
+    buffer.write_u32_le(central_directory_size);
+    buffer.write_u32_le(central_directory_offset);
+    buffer.write_u16_le(0);
+
+    fs::write(filename, buffer)?;
+    Ok(())
+}
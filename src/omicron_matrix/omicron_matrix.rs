@@ -64,6 +64,7 @@ pub fn read_omicron_matrix(filename: &str) -> Result<OmicronMatrix> {
             yres: paraminfo.yres,
             xsize: paraminfo.xsize,
             ysize: paraminfo.ysize,
+            z_unit: "m".to_string(),
             img_data: v_fw,
         },
         img_data_bw: SpmImage {
@@ -72,6 +73,7 @@ pub fn read_omicron_matrix(filename: &str) -> Result<OmicronMatrix> {
             yres: paraminfo.yres,
             xsize: paraminfo.xsize,
             ysize: paraminfo.ysize,
+            z_unit: "m".to_string(),
             img_data: backward_up,
         },
     })
@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::spm_image::SpmImage;
+
+/// Omicron SCALA image, a `.par` parameter file and a big-endian int16 file for each
+/// channel and direction, e.g. `.tf0`, `.tb0`, `.tf1`
+#[derive(Debug)]
+pub struct ScalaImage {
+    pub filepath: PathBuf,
+    pub img_id: String,
+    pub date: String,
+    pub comment: String,
+    /// Size in pixels
+    pub xres: usize,
+    /// Size in pixels
+    pub yres: usize,
+    /// Size in nm
+    pub xsize: f64,
+    /// Size in nm
+    pub ysize: f64,
+    /// Offset in nm
+    pub xoffset: f64,
+    /// Offset in nm
+    pub yoffset: f64,
+    /// Rotation in degrees
+    pub scan_angle: f64,
+    /// Bias in V
+    pub bias: f64,
+    /// Current setpoint in nA
+    pub current: f64,
+    /// `Key : value` parameters, e.g. `Loop Gain` -> `1.500000`
+    pub header: BTreeMap<String, String>,
+    pub channel_info: Vec<ScalaChannelInfo>,
+    /// Images in the order of the `Topographic Channel` entries
+    pub channels: Vec<SpmImage>,
+}
+
+/// `Topographic Channel` entry of the `.par` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalaChannelInfo {
+    /// E.g. `Z`
+    pub channel: String,
+    /// `Forward` or `Backward`
+    pub direction: String,
+    pub min_raw: f64,
+    pub max_raw: f64,
+    pub min_phys: f64,
+    pub max_phys: f64,
+    /// Physical value of one raw step
+    pub resolution: f64,
+    pub unit: String,
+    /// Name of the data file, relative to the `.par` file
+    pub filename: String,
+    /// E.g. `Constant Current`
+    pub display_name: String,
+}
+
+impl ScalaChannelInfo {
+    /// The values follow the `Topographic Channel` line in a fixed order, as read by
+    /// Gwyddion's `omicron.c`
+    fn from_values(channel: &str, values: &[String]) -> Result<Self> {
+        if values.len() < 9 {
+            return Err(anyhow!(
+                "Expected 9 values for channel {}, got {}",
+                channel,
+                values.len()
+            ));
+        }
+        let get_f64 = |i: usize, name: &str| -> Result<f64> {
+            values[i]
+                .parse::<f64>()
+                .map_err(|e| anyhow!("Invalid {} for channel {}: {}", name, channel, e))
+        };
+        Ok(Self {
+            channel: channel.to_string(),
+            direction: values[0].clone(),
+            min_raw: get_f64(1, "minimum raw value")?,
+            max_raw: get_f64(2, "maximum raw value")?,
+            min_phys: get_f64(3, "minimum physical value")?,
+            max_phys: get_f64(4, "maximum physical value")?,
+            resolution: get_f64(5, "resolution")?,
+            unit: values[6].clone(),
+            filename: values[7].clone(),
+            display_name: values[8].clone(),
+        })
+    }
+
+    /// Maps the raw range linearly onto the physical range
+    fn to_physical(&self, raw: i16) -> f64 {
+        let scale = (self.max_phys - self.min_phys) / (self.max_raw - self.min_raw);
+        self.min_phys + (f64::from(raw) - self.min_raw) * scale
+    }
+}
+
+const TOPO_CHANNEL: &str = "Topographic Channel";
+const SPEC_CHANNEL: &str = "Spectroscopy Channel";
+
+/// Line of the `.par` file, comments after `;` dropped
+#[derive(Debug, PartialEq)]
+enum ParLine {
+    /// `Key : value ;[unit]`
+    Param(String, String),
+    /// Indented `value ;description` line of the channel above
+    Value(String),
+}
+
+fn parse_par_line(line: &str) -> Option<ParLine> {
+    let content = line.split(';').next()?;
+    if content.trim().is_empty() {
+        return None;
+    }
+    // Filenames of the channel values may contain `:`, but are indented
+    if line.starts_with(char::is_whitespace) {
+        return Some(ParLine::Value(content.trim().to_string()));
+    }
+    match content.split_once(':') {
+        Some((key, value)) => Some(ParLine::Param(
+            key.trim().to_string(),
+            value.trim().to_string(),
+        )),
+        None => Some(ParLine::Value(content.trim().to_string())),
+    }
+}
+
+/// The data files are named in the `.par` file, which was written on Windows and may
+/// differ in case from the files on disk
+fn find_data_file(dir: &Path, filename: &str) -> Result<PathBuf> {
+    let path = dir.join(filename);
+    if path.exists() {
+        return Ok(path);
+    }
+    fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.eq_ignore_ascii_case(filename))
+        })
+        .ok_or_else(|| anyhow!("Data file {} not found", path.display()))
+}
+
+pub fn read_scala(filename: &str) -> Result<ScalaImage> {
+    let bytes = fs::read(filename)?;
+    let text = String::from_utf8_lossy(&bytes);
+
+    // `Topographic Channel` and `Spectroscopy Channel` lines are followed by their
+    // values, one per line
+    let mut header = BTreeMap::new();
+    let mut blocks: Vec<(String, String, Vec<String>)> = Vec::new();
+    for line in text.lines().filter_map(parse_par_line) {
+        match line {
+            ParLine::Param(key, value) if key == TOPO_CHANNEL || key == SPEC_CHANNEL => {
+                blocks.push((key, value, Vec::new()));
+            }
+            ParLine::Param(key, value) => {
+                header.insert(key, value);
+            }
+            ParLine::Value(value) => {
+                if let Some((_, _, values)) = blocks.last_mut() {
+                    values.push(value);
+                }
+            }
+        }
+    }
+
+    let get = |key: &str| header.get(key).map(String::as_str).unwrap_or_default();
+    let get_f64 = |key: &str| get(key).parse::<f64>().unwrap_or_default();
+    let xres: usize = get("Image Size in X").parse()?;
+    let yres: usize = get("Image Size in Y").parse()?;
+
+    let channel_info = blocks
+        .iter()
+        .filter(|(kind, _, _)| kind == TOPO_CHANNEL)
+        .map(|(_, channel, values)| ScalaChannelInfo::from_values(channel, values))
+        .collect::<Result<Vec<_>>>()?;
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+    let dir = filepath.parent().unwrap_or(Path::new(""));
+
+    let num_pixels = xres * yres;
+    let mut channels = Vec::new();
+    for info in channel_info.iter() {
+        let data = fs::read(find_data_file(dir, &info.filename)?)?;
+        if data.len() < num_pixels * 2 {
+            return Err(anyhow!(
+                "Expected {}x{} pixels in {}, got {} bytes",
+                xres,
+                yres,
+                info.filename,
+                data.len()
+            ));
+        }
+        let img_data = data[..num_pixels * 2]
+            .chunks_exact(2)
+            .map(|x| info.to_physical(i16::from_be_bytes([x[0], x[1]])))
+            .collect();
+        let direction = if info.direction == "Backward" {
+            "bwd"
+        } else {
+            "fwd"
+        };
+        channels.push(SpmImage {
+            img_id: format!("{}_{}_{}", basename, info.channel, direction),
+            xres,
+            yres,
+            xsize: get_f64("Field X Size in nm"),
+            ysize: get_f64("Field Y Size in nm"),
+            z_unit: info.unit.clone(),
+            img_data,
+        });
+    }
+
+    Ok(ScalaImage {
+        img_id: basename.to_string(),
+        date: get("Date").to_string(),
+        comment: get("Comment").to_string(),
+        xres,
+        yres,
+        xsize: get_f64("Field X Size in nm"),
+        ysize: get_f64("Field Y Size in nm"),
+        xoffset: get_f64("X Offset"),
+        yoffset: get_f64("Y Offset"),
+        scan_angle: get_f64("Scan Angle"),
+        bias: get_f64("Gap Voltage"),
+        current: get_f64("Feedback Set"),
+        channel_info,
+        channels,
+        filepath,
+        header,
+    })
+}
@@ -0,0 +1,200 @@
+use std::fs::File;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use tiff::decoder::{Decoder, Limits};
+use tiff::tags::Tag;
+
+use crate::spm_image::flip_img_data;
+use crate::spm_image::SpmImage;
+use crate::utils::Bytereading;
+
+/// Park Systems TIFF (`.tiff`), the TIFF image is an 8 bit preview, the raw data and
+/// its header are stored in private tags
+#[derive(Debug)]
+pub struct ParkImage {
+    pub filepath: PathBuf,
+    /// E.g. `Topography`
+    pub source_name: String,
+    /// E.g. `NCM`
+    pub image_mode: String,
+    /// Size in pixels
+    pub xres: usize,
+    /// Size in pixels
+    pub yres: usize,
+    /// Size in nm
+    pub xsize: f64,
+    /// Size in nm
+    pub ysize: f64,
+    /// Offset in nm
+    pub xoffset: f64,
+    /// Offset in nm
+    pub yoffset: f64,
+    /// Rotation in degrees
+    pub scan_angle: f64,
+    /// Scan rate in Hz
+    pub scan_rate: f64,
+    pub set_point: f64,
+    pub set_point_unit: String,
+    /// Bias in V
+    pub tip_bias: f64,
+    /// Bias in V
+    pub sample_bias: f64,
+    pub forward: bool,
+    pub scan_up: bool,
+    /// Factor from the raw data to `z_unit`
+    pub data_gain: f64,
+    /// Not applied to `img_data`, like in Gwyddion
+    pub z_scale: f64,
+    /// Not applied to `img_data`, like in Gwyddion
+    pub z_offset: f64,
+    pub img_data: SpmImage,
+}
+
+const PARK_MAGIC: u32 = 0x0E03_1301;
+const TAG_MAGIC: Tag = Tag::Unknown(50432);
+const TAG_DATA: Tag = Tag::Unknown(50434);
+const TAG_HEADER: Tag = Tag::Unknown(50435);
+// Header up to the xy servo mode, the data type follows in newer versions
+const HEADER_SIZE: usize = 348;
+
+/// Fixed-length UTF-16 string, padded with NUL
+fn read_utf16_fixed(cursor: &mut Cursor<&[u8]>, length: usize) -> String {
+    let chars: Vec<u16> = (0..length).map(|_| cursor.read_u16_le()).collect();
+    String::from_utf16_lossy(&chars)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Units are written as `um` for µm
+fn park_unit(unit: &str) -> String {
+    match unit {
+        "um" => "\u{b5}m".to_string(),
+        unit => unit.to_string(),
+    }
+}
+
+pub fn read_park_tiff(filename: &str) -> Result<ParkImage> {
+    let mut decoder = Decoder::new(File::open(filename)?)?.with_limits(Limits::unlimited());
+    if decoder.find_tag_unsigned::<u32>(TAG_MAGIC)? != Some(PARK_MAGIC) {
+        return Err(anyhow!("Not a Park Systems TIFF: {}", filename));
+    }
+    let mut byte_tag = |tag: Tag| -> Result<Vec<u8>> {
+        decoder
+            .find_tag_unsigned_vec::<u8>(tag)?
+            .ok_or_else(|| anyhow!("No tag {:?} in {}", tag, filename))
+    };
+    let header = byte_tag(TAG_HEADER)?;
+    let data = byte_tag(TAG_DATA)?;
+    if header.len() < HEADER_SIZE {
+        return Err(anyhow!("Header of {} bytes is too short", header.len()));
+    }
+
+    let mut cursor = Cursor::new(header.as_slice());
+    let _image_type = cursor.read_i32_le();
+    let source_name = read_utf16_fixed(&mut cursor, 32);
+    let image_mode = read_utf16_fixed(&mut cursor, 8);
+    let _lpf_strength = cursor.read_f64_le();
+    let _auto_flatten = cursor.read_i32_le();
+    let _ac_track = cursor.read_i32_le();
+    let xres = cursor.read_i32_le().max(0) as usize;
+    let yres = cursor.read_i32_le().max(0) as usize;
+    let scan_angle = cursor.read_f64_le();
+    let _sine_scan = cursor.read_i32_le();
+    let _overscan_rate = cursor.read_f64_le();
+    let forward = cursor.read_i32_le() != 0;
+    let scan_up = cursor.read_i32_le() != 0;
+    let _swap_xy = cursor.read_i32_le();
+    // Sizes and offsets in µm
+    let xsize = cursor.read_f64_le() * 1e3;
+    let ysize = cursor.read_f64_le() * 1e3;
+    let xoffset = cursor.read_f64_le() * 1e3;
+    let yoffset = cursor.read_f64_le() * 1e3;
+    let scan_rate = cursor.read_f64_le();
+    let set_point = cursor.read_f64_le();
+    let set_point_unit = park_unit(&read_utf16_fixed(&mut cursor, 8));
+    let tip_bias = cursor.read_f64_le();
+    let sample_bias = cursor.read_f64_le();
+    let data_gain = cursor.read_f64_le();
+    let z_scale = cursor.read_f64_le();
+    let z_offset = cursor.read_f64_le();
+    let z_unit = park_unit(&read_utf16_fixed(&mut cursor, 8));
+
+    // 0 for int16, 1 for int32 and 2 for float32 data
+    let data_type = if header.len() >= HEADER_SIZE + 4 {
+        cursor.set_position(HEADER_SIZE as u64);
+        cursor.read_i32_le()
+    } else {
+        0
+    };
+    let num_pixels = xres * yres;
+    let bytes_per_pixel = if data_type == 0 { 2 } else { 4 };
+    if data.len() < num_pixels * bytes_per_pixel {
+        return Err(anyhow!(
+            "Expected {}x{} pixels, got {} bytes",
+            xres,
+            yres,
+            data.len()
+        ));
+    }
+    let data = &data[..num_pixels * bytes_per_pixel];
+    let raw: Vec<f64> = match data_type {
+        0 => data
+            .chunks_exact(2)
+            .map(|x| f64::from(i16::from_le_bytes([x[0], x[1]])))
+            .collect(),
+        1 => data
+            .chunks_exact(4)
+            .map(|x| f64::from(i32::from_le_bytes(x.try_into().unwrap())))
+            .collect(),
+        2 => data
+            .chunks_exact(4)
+            .map(|x| f64::from(f32::from_le_bytes(x.try_into().unwrap())))
+            .collect(),
+        other => return Err(anyhow!("Unknown data type {}", other)),
+    };
+    // Only the data gain scales the values, like in Gwyddion, `z_scale` and `z_offset`
+    // are kept as read. The first line in the data is the bottom of the image.
+    let img_data = flip_img_data(
+        raw.iter().map(|v| v * data_gain).collect(),
+        xres as u32,
+        yres as u32,
+    );
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+    let direction = if forward { "fwd" } else { "bwd" };
+
+    Ok(ParkImage {
+        img_data: SpmImage {
+            img_id: format!("{}_{}_{}", basename, source_name, direction),
+            xres,
+            yres,
+            xsize,
+            ysize,
+            z_unit,
+            img_data,
+        },
+        filepath,
+        source_name,
+        image_mode,
+        xres,
+        yres,
+        xsize,
+        ysize,
+        xoffset,
+        yoffset,
+        scan_angle,
+        scan_rate,
+        set_point,
+        set_point_unit,
+        tip_bias,
+        sample_bias,
+        forward,
+        scan_up,
+        data_gain,
+        z_scale,
+        z_offset,
+    })
+}
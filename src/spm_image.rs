@@ -1,9 +1,13 @@
-use std::io::Cursor;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Cursor};
 
 use anyhow::Context;
 use image::{ImageBuffer, Luma};
 use linfa_linalg::qr::LeastSquaresQr;
 use ndarray::{Array, Array2, ArrayView, Axis, s};
+use tiff::encoder::{colortype, Rational, TiffEncoder, TiffValue};
+use tiff::tags::{ResolutionUnit, Tag};
 
 use crate::rocket::ROCKET;
 
@@ -16,9 +20,21 @@ pub struct SpmImage {
     pub xres: usize,
     /// Resolution in y-axis, corresponds to number of scan lines
     pub yres: usize,
+    /// Unit of the values in `img_data`
+    pub z_unit: String,
     pub img_data: Vec<f64>,
 }
 
+/// Sample format of [`SpmImage::save_tiff`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TiffSampleFormat {
+    /// Values in `z_unit`
+    #[default]
+    Float32,
+    /// Values scaled from minimum to maximum, `c0 + c1 * value` in `z_unit`
+    Uint16,
+}
+
 impl SpmImage {
     fn norm(&self) -> Vec<u8> {
         let min = self
@@ -95,6 +111,95 @@ impl SpmImage {
         .ok();
     }
 
+    /// Writes the image as TIFF with the pixel size in nm and an ImageJ description,
+    /// so ImageJ / Fiji show calibrated sizes and values
+    ///
+    /// The entries of `metadata` are appended to the description as `key=value` lines.
+    pub fn save_tiff(
+        &self,
+        filename: &str,
+        sample_format: TiffSampleFormat,
+        metadata: &BTreeMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let (min, max) = self
+            .img_data
+            .iter()
+            .filter(|x| x.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), x| {
+                (min.min(*x), max.max(*x))
+            });
+        // Without finite values the range is the one of a constant image of zeros
+        let (min, max) = if min <= max { (min, max) } else { (0.0, 0.0) };
+
+        let mut description = vec!["ImageJ=1.11a".to_string(), "unit=nm".to_string()];
+        let scale = match sample_format {
+            TiffSampleFormat::Float32 => {
+                description.push(format!("min={}", min));
+                description.push(format!("max={}", max));
+                None
+            }
+            TiffSampleFormat::Uint16 => {
+                let scale = if max > min { (max - min) / 65535.0 } else { 1.0 };
+                description.push("cf=0".to_string());
+                description.push(format!("c0={}", min));
+                description.push(format!("c1={}", scale));
+                description.push(format!("vunit={}", self.z_unit));
+                Some(scale)
+            }
+        };
+        description.push(format!("img_id={}", self.img_id));
+        description.push(format!("xsize={}", self.xsize));
+        description.push(format!("ysize={}", self.ysize));
+        description.push(format!("z_unit={}", self.z_unit));
+        for (key, value) in metadata {
+            description.push(format!("{}={}", key, value.replace('\n', " ")));
+        }
+        let description = tiff_ascii(&description.join("\n"));
+
+        let mut encoder = TiffEncoder::new(BufWriter::new(File::create(filename)?))?;
+        match scale {
+            None => {
+                let data: Vec<f32> = self.img_data.iter().map(|x| *x as f32).collect();
+                self.write_tiff_image::<colortype::Gray32Float>(&mut encoder, &description, &data)
+            }
+            Some(scale) => {
+                // Not finite values are written as the minimum
+                let data: Vec<u16> = self
+                    .img_data
+                    .iter()
+                    .map(|x| {
+                        if x.is_finite() {
+                            ((x - min) / scale).round() as u16
+                        } else {
+                            0
+                        }
+                    })
+                    .collect();
+                self.write_tiff_image::<colortype::Gray16>(&mut encoder, &description, &data)
+            }
+        }
+    }
+
+    fn write_tiff_image<C>(
+        &self,
+        encoder: &mut TiffEncoder<BufWriter<File>>,
+        description: &str,
+        data: &[C::Inner],
+    ) -> anyhow::Result<()>
+    where
+        C: colortype::ColorType,
+        [C::Inner]: TiffValue,
+    {
+        let mut image = encoder.new_image::<C>(self.xres as u32, self.yres as u32)?;
+        image.encoder().write_tag(Tag::ImageDescription, description)?;
+        image.encoder().write_tag(Tag::Software, "spm-rs")?;
+        image.x_resolution(pixels_per_nm(self.xres, self.xsize));
+        image.y_resolution(pixels_per_nm(self.yres, self.ysize));
+        image.resolution_unit(ResolutionUnit::None);
+        image.write_data(data)?;
+        Ok(())
+    }
+
     pub fn correct_plane(&mut self) -> &Self {
         let xres = self.xres;
         let yres = self.yres;
@@ -142,6 +247,32 @@ impl SpmImage {
     }
 }
 
+/// TIFF ASCII tags may only contain ASCII, e.g. `µm` is written as `um`
+fn tiff_ascii(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\u{b5}' => "u".to_string(),
+            '\u{c5}' => "Angstrom".to_string(),
+            '\0' => " ".to_string(),
+            c if c.is_ascii() => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+/// Resolution as pixels per nm
+fn pixels_per_nm(res: usize, size: f64) -> Rational {
+    let value = if size > 0.0 { res as f64 / size } else { 1.0 };
+    let mut d = 1_000_000u32;
+    while value * d as f64 > u32::MAX as f64 && d > 1 {
+        d /= 10;
+    }
+    Rational {
+        n: (value * d as f64).round() as u32,
+        d,
+    }
+}
+
 pub fn flip_img_data(img_data: Vec<f64>, xres: u32, yres: u32) -> Vec<f64> {
     let mut flipped: Vec<f64> = Vec::with_capacity((xres * yres) as usize);
     for i in (0..yres).rev() {
@@ -0,0 +1,39 @@
+#[derive(Debug)]
+pub struct SpmSpectrum {
+    pub spec_id: String,
+    /// Name of the swept quantity, e.g. `Bias` or `Time`
+    pub x_label: String,
+    pub x_unit: String,
+    pub x_data: Vec<f64>,
+    /// Measured signals, each with the same length as `x_data`
+    pub channels: Vec<SpectrumChannel>,
+    /// Tip position in nm
+    pub xpos: f64,
+    /// Tip position in nm
+    pub ypos: f64,
+}
+
+#[derive(Debug)]
+pub struct SpectrumChannel {
+    pub name: String,
+    pub unit: String,
+    pub data: Vec<f64>,
+}
+
+impl SpmSpectrum {
+    pub fn channel(&self, name: &str) -> Option<&SpectrumChannel> {
+        self.channels.iter().find(|c| c.name == name)
+    }
+}
+
+/// `num` evenly spaced values from `start` to `stop`, both included
+pub fn linspace(start: f64, stop: f64, num: usize) -> Vec<f64> {
+    match num {
+        0 => Vec::new(),
+        1 => vec![start],
+        _ => {
+            let step = (stop - start) / (num - 1) as f64;
+            (0..num).map(|i| start + i as f64 * step).collect()
+        }
+    }
+}
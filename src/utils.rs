@@ -104,6 +104,44 @@ impl Bytereading for Cursor<&[u8]> {
     }
 }
 
+pub trait Bytewriting {
+    fn write_string(&mut self, s: &str, length: usize);
+    fn write_i16_le(&mut self, n: i16);
+    fn write_u16_le(&mut self, n: u16);
+    fn write_i32_le(&mut self, n: i32);
+    fn write_u32_le(&mut self, n: u32);
+    fn write_f64_le(&mut self, n: f64);
+}
+
+impl Bytewriting for Vec<u8> {
+    // Pads with NUL or truncates to exactly `length` bytes
+    fn write_string(&mut self, s: &str, length: usize) {
+        let mut buffer = s.as_bytes().to_vec();
+        buffer.resize(length, 0);
+        self.extend_from_slice(&buffer);
+    }
+
+    fn write_i16_le(&mut self, n: i16) {
+        self.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn write_u16_le(&mut self, n: u16) {
+        self.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn write_i32_le(&mut self, n: i32) {
+        self.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn write_u32_le(&mut self, n: u32) {
+        self.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn write_f64_le(&mut self, n: f64) {
+        self.extend_from_slice(&n.to_le_bytes());
+    }
+}
+
 pub fn read_utf16_bytes(slice: &[u8]) -> String {
     let iter = (0..(slice.len() / 2)).map(|i| u16::from_le_bytes([slice[2 * i], slice[2 * i + 1]]));
     let result = std::char::decode_utf16(iter)
@@ -161,6 +199,43 @@ fn read_f64_le_bytes(buffer: &[u8]) -> f64 {
     f64::from_le_bytes(buffer[..8].try_into().unwrap())
 }
 
+/// Factor from a length unit to nm, `None` for units that are not lengths
+pub(crate) fn length_to_nm(unit: &str) -> Option<f64> {
+    match unit {
+        "m" => Some(1e9),
+        "cm" => Some(1e7),
+        "mm" => Some(1e6),
+        "\u{b5}m" | "um" => Some(1e3),
+        "nm" => Some(1.0),
+        "\u{c5}" | "A" => Some(0.1),
+        "pm" => Some(1e-3),
+        _ => None,
+    }
+}
+
+/// Unit without prefix and the factor to it, e.g. `nm` -> (`m`, 1e-9). Units that are
+/// not lengths, currents or voltages are returned unchanged.
+pub(crate) fn to_base_unit(unit: &str) -> (String, f64) {
+    if unit == "\u{c5}" {
+        return ("m".to_string(), 1e-10);
+    }
+    let prefixes = [
+        ("p", 1e-12),
+        ("n", 1e-9),
+        ("\u{b5}", 1e-6),
+        ("u", 1e-6),
+        ("m", 1e-3),
+    ];
+    for (prefix, factor) in prefixes {
+        if let Some(base) = unit.strip_prefix(prefix) {
+            if ["m", "A", "V"].contains(&base) {
+                return (base.to_string(), factor);
+            }
+        }
+    }
+    (unit.to_string(), 1.0)
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -337,6 +412,46 @@ mod tests {
         assert_eq!(cursor.read_f64_le(), c);
     }
 
+    #[test]
+    fn test_write_string() {
+        let mut buffer: Vec<u8> = Vec::new();
+        buffer.write_string("Test", 6);
+        buffer.write_string("string", 3);
+        assert_eq!(buffer, b"Test\0\0str");
+    }
+
+    #[test]
+    fn test_write_i16_le() {
+        let mut buffer: Vec<u8> = Vec::new();
+        buffer.write_i16_le(-42);
+        let mut cursor = Cursor::new(buffer.as_slice());
+        assert_eq!(cursor.read_i16_le(), -42);
+    }
+
+    #[test]
+    fn test_write_i32_le() {
+        let mut buffer: Vec<u8> = Vec::new();
+        buffer.write_i32_le(-42);
+        let mut cursor = Cursor::new(buffer.as_slice());
+        assert_eq!(cursor.read_i32_le(), -42);
+    }
+
+    #[test]
+    fn test_write_u32_le() {
+        let mut buffer: Vec<u8> = Vec::new();
+        buffer.write_u32_le(42);
+        let mut cursor = Cursor::new(buffer.as_slice());
+        assert_eq!(cursor.read_u32_le(), 42);
+    }
+
+    #[test]
+    fn test_write_f64_le() {
+        let mut buffer: Vec<u8> = Vec::new();
+        buffer.write_f64_le(-4.2);
+        let mut cursor = Cursor::new(buffer.as_slice());
+        assert_eq!(cursor.read_f64_le(), -4.2);
+    }
+
     #[test]
     fn test_read_magic_header() {
         let h = "MAGICXHEADER";
@@ -359,4 +474,23 @@ mod tests {
         let mut cursor = Cursor::new(buffer.as_slice());
         assert_eq!(cursor.read_string(4), "Test");
     }
+
+    #[test]
+    fn test_length_to_nm() {
+        assert_eq!(length_to_nm("\u{b5}m"), Some(1e3));
+        assert_eq!(length_to_nm("\u{c5}"), Some(0.1));
+        assert_eq!(length_to_nm("nm"), Some(1.0));
+        assert_eq!(length_to_nm("bohr"), None);
+        assert_eq!(length_to_nm("px"), None);
+        assert_eq!(length_to_nm(""), None);
+    }
+
+    #[test]
+    fn test_to_base_unit() {
+        assert_eq!(to_base_unit("nm"), ("m".to_string(), 1e-9));
+        assert_eq!(to_base_unit("\u{c5}"), ("m".to_string(), 1e-10));
+        assert_eq!(to_base_unit("pA"), ("A".to_string(), 1e-12));
+        assert_eq!(to_base_unit("mV"), ("V".to_string(), 1e-3));
+        assert_eq!(to_base_unit("Hz"), ("Hz".to_string(), 1.0));
+    }
 }
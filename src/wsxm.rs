@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::spm_image::flip_img_data;
+use crate::spm_image::SpmImage;
+use crate::spm_spectrum::{SpectrumChannel, SpmSpectrum};
+use crate::utils::length_to_nm;
+
+/// Sections of a WSxM header, e.g. `Control` -> `X Amplitude` -> `100 nm`
+pub type WsxmHeader = BTreeMap<String, BTreeMap<String, String>>;
+
+/// WSxM image (`.top`, `.stp`), a text header followed by binary data
+#[derive(Debug)]
+pub struct WsxmImage {
+    pub filepath: PathBuf,
+    pub img_id: String,
+    pub header: WsxmHeader,
+    /// E.g. `Topography`
+    pub channel: String,
+    /// Size in pixels
+    pub xres: usize,
+    /// Size in pixels
+    pub yres: usize,
+    /// Size in nm
+    pub xsize: f64,
+    /// Size in nm
+    pub ysize: f64,
+    /// Offset in nm
+    pub xoffset: f64,
+    /// Offset in nm
+    pub yoffset: f64,
+    /// Rotation in degrees
+    pub rotation: f64,
+    /// Bias in V
+    pub bias: f64,
+    pub set_point: f64,
+    pub set_point_unit: String,
+    pub img_data: SpmImage,
+}
+
+/// WSxM curve (`.cur`), a text header followed by columns of text
+#[derive(Debug)]
+pub struct WsxmCurve {
+    pub filepath: PathBuf,
+    pub header: WsxmHeader,
+    pub spectrum: SpmSpectrum,
+}
+
+const HEADER_SIZE: &str = "Image header size:";
+const HEADER_END: &str = "[Header end]";
+
+/// The header is Latin-1 encoded, e.g. for `Å`
+fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+fn parse_wsxm_header(text: &str) -> WsxmHeader {
+    let mut header = WsxmHeader::new();
+    let mut section = String::new();
+    for line in text.lines().map(str::trim) {
+        if line == HEADER_END {
+            break;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.to_string();
+        } else if let Some((key, value)) = line.split_once(": ") {
+            header
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    header
+}
+
+/// Splits a value like `100 nm` into number and unit
+fn parse_quantity(value: &str) -> Option<(f64, String)> {
+    let mut parts = value.split_whitespace();
+    let number = parts.next()?.parse().ok()?;
+    Some((number, parts.next().unwrap_or_default().to_string()))
+}
+
+fn voltage_to_volt(unit: &str) -> f64 {
+    match unit {
+        "mV" => 1e-3,
+        "\u{b5}V" | "uV" => 1e-6,
+        _ => 1.0,
+    }
+}
+
+/// Splits the file at the size given in the header and parses the header
+fn split_wsxm(filename: &str) -> Result<(WsxmHeader, Vec<u8>)> {
+    let mut bytes = fs::read(filename)?;
+    let start = decode_latin1(&bytes[..bytes.len().min(256)]);
+    let header_size: usize = start
+        .lines()
+        .find_map(|line| line.strip_prefix(HEADER_SIZE))
+        .ok_or_else(|| anyhow!("No header size found in {}", filename))?
+        .trim()
+        .parse()?;
+    if header_size > bytes.len() {
+        return Err(anyhow!("Header size {} exceeds file length", header_size));
+    }
+    let data = bytes.split_off(header_size);
+    Ok((parse_wsxm_header(&decode_latin1(&bytes)), data))
+}
+
+pub fn read_wsxm_image(filename: &str) -> Result<WsxmImage> {
+    let (header, data) = split_wsxm(filename)?;
+
+    let get = |section: &str, key: &str| -> &str {
+        header
+            .get(section)
+            .and_then(|s| s.get(key))
+            .map(String::as_str)
+            .unwrap_or_default()
+    };
+    let quantity = |section: &str, key: &str| parse_quantity(get(section, key));
+    let length = |key: &str| -> Result<f64> {
+        match quantity("Control", key) {
+            Some((value, unit)) => length_to_nm(&unit)
+                .map(|factor| value * factor)
+                .ok_or_else(|| anyhow!("Unknown unit {} of {}", unit, key)),
+            None => Ok(0.0),
+        }
+    };
+
+    let xres: usize = get("General Info", "Number of columns").parse()?;
+    let yres: usize = get("General Info", "Number of rows").parse()?;
+
+    let xsize = length("X Amplitude")?;
+    let ysize = length("Y Amplitude")?;
+    let xoffset = length("X Offset")?;
+    let yoffset = length("Y Offset")?;
+    let rotation = quantity("Control", "Rotation")
+        .map(|(value, _)| value)
+        .unwrap_or_default();
+    let bias = quantity("Control", "Topography Bias")
+        .or_else(|| quantity("Control", "Bias"))
+        .map(|(value, unit)| value * voltage_to_volt(&unit))
+        .unwrap_or_default();
+    let (set_point, set_point_unit) = quantity("Control", "Set Point").unwrap_or_default();
+    let (z_amplitude, z_unit) =
+        quantity("General Info", "Z Amplitude").unwrap_or((1.0, String::new()));
+    let channel = get("General Info", "Acquisition channel").to_string();
+
+    // Integer data spans the Z amplitude, floating point data is in its unit
+    let num_pixels = xres * yres;
+    let raw: Vec<f64> = match get("General Info", "Image Data Type") {
+        "double" => data
+            .chunks_exact(8)
+            .map(|x| f64::from_le_bytes(x.try_into().unwrap()))
+            .collect(),
+        "float" => data
+            .chunks_exact(4)
+            .map(|x| f64::from(f32::from_le_bytes(x.try_into().unwrap())))
+            .collect(),
+        "integer" => data
+            .chunks_exact(4)
+            .map(|x| {
+                f64::from(i32::from_le_bytes(x.try_into().unwrap())) * z_amplitude / 2_f64.powi(32)
+            })
+            .collect(),
+        "short" | "" => data
+            .chunks_exact(2)
+            .map(|x| f64::from(i16::from_le_bytes(x.try_into().unwrap())) * z_amplitude / 65536.0)
+            .collect(),
+        other => return Err(anyhow!("Unknown data type {} in {}", other, filename)),
+    };
+    if raw.len() < num_pixels {
+        return Err(anyhow!(
+            "Expected {}x{} pixels, got {}",
+            xres,
+            yres,
+            raw.len()
+        ));
+    }
+    // The first row in the file is the bottom of the image
+    let img_data = flip_img_data(raw[..num_pixels].to_vec(), xres as u32, yres as u32);
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+    let img_id = basename.to_string();
+
+    Ok(WsxmImage {
+        filepath,
+        img_id: img_id.clone(),
+        channel,
+        xres,
+        yres,
+        xsize,
+        ysize,
+        xoffset,
+        yoffset,
+        rotation,
+        bias,
+        set_point,
+        set_point_unit,
+        img_data: SpmImage {
+            img_id,
+            xres,
+            yres,
+            xsize,
+            ysize,
+            z_unit,
+            img_data,
+        },
+        header,
+    })
+}
+
+/// Axis label without the WSxM placeholders, e.g. `Voltage [#x]`
+fn axis_label(text: &str) -> String {
+    match text.split_once('[') {
+        Some((label, _)) => label.trim().to_string(),
+        None => text.trim().to_string(),
+    }
+}
+
+pub fn read_wsxm_curve(filename: &str) -> Result<WsxmCurve> {
+    let (header, data) = split_wsxm(filename)?;
+
+    let get = |key: &str| -> &str {
+        header
+            .get("General Info")
+            .and_then(|s| s.get(key))
+            .map(String::as_str)
+            .unwrap_or_default()
+    };
+    let num_lines: usize = get("Number of lines").parse().unwrap_or(1);
+
+    let mut columns: Vec<Vec<f64>> = Vec::new();
+    for line in decode_latin1(&data)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+    {
+        let values: Vec<f64> = line
+            .split_whitespace()
+            .map(|x| x.parse::<f64>())
+            .collect::<Result<_, _>>()?;
+        if columns.is_empty() {
+            columns = vec![Vec::new(); values.len()];
+        }
+        if values.len() != columns.len() {
+            return Err(anyhow!("Inconsistent number of columns in line `{}`", line));
+        }
+        for (column, value) in columns.iter_mut().zip(values) {
+            column.push(value);
+        }
+    }
+    if columns.is_empty() {
+        return Err(anyhow!("No data in {}", filename));
+    }
+
+    // Either one x column shared by all lines or an x column for each line
+    let paired = columns.len() == 2 * num_lines && num_lines > 1;
+    let y_label = axis_label(get("Y axis text"));
+    let y_unit = get("Y axis unit").to_string();
+    let mut columns = columns.into_iter();
+    let x_data = columns.next().unwrap();
+    let channels = columns
+        .enumerate()
+        .filter(|(i, _)| !paired || i % 2 == 0)
+        .map(|(_, data)| data)
+        .enumerate()
+        .map(|(i, data)| SpectrumChannel {
+            name: if num_lines > 1 {
+                format!("{} {}", y_label, i + 1)
+            } else {
+                y_label.clone()
+            },
+            unit: y_unit.clone(),
+            data,
+        })
+        .collect();
+
+    let filepath = PathBuf::from(filename);
+    let basename = filepath.file_stem().unwrap().to_str().unwrap();
+
+    let spectrum = SpmSpectrum {
+        spec_id: basename.to_string(),
+        x_label: axis_label(get("X axis text")),
+        x_unit: get("X axis unit").to_string(),
+        x_data,
+        channels,
+        xpos: 0.0,
+        ypos: 0.0,
+    };
+
+    Ok(WsxmCurve {
+        filepath,
+        header,
+        spectrum,
+    })
+}
@@ -0,0 +1,174 @@
+use spm_rs::ascii_import::{read_ascii, AsciiLayout, AsciiOptions};
+
+#[test]
+fn test_matrix() {
+    let ascii = read_ascii(
+        "tests/test_files/ascii_matrix.txt",
+        &AsciiOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(ascii.layout, AsciiLayout::Matrix);
+    assert_eq!(ascii.delimiter, Some('\t'));
+    assert_eq!(ascii.header.len(), 3);
+    assert!(!ascii.resampled);
+
+    let img = &ascii.img_data;
+    assert_eq!(img.img_id, "ascii_matrix");
+    assert_eq!((img.xres, img.yres), (4, 3));
+    // Size from the header
+    assert_eq!((img.xsize, img.ysize), (2.0, 1.5));
+    assert_eq!(img.img_data[0], 0.1);
+    assert_eq!(img.img_data[11], 2.4);
+}
+
+#[test]
+fn test_matrix_options() {
+    let options = AsciiOptions {
+        xsize: Some(10.0),
+        ysize: Some(7.5),
+        z_unit: "A".to_string(),
+        ..Default::default()
+    };
+    let img = read_ascii("tests/test_files/ascii_matrix.txt", &options)
+        .unwrap()
+        .img_data;
+    assert_eq!((img.xsize, img.ysize), (10.0, 7.5));
+    assert_eq!(img.z_unit, "A");
+}
+
+#[test]
+fn test_xyz_grid() {
+    let options = AsciiOptions {
+        xy_unit: "\u{c5}".to_string(),
+        ..Default::default()
+    };
+    let ascii = read_ascii("tests/test_files/ascii_xyz.csv", &options).unwrap();
+    assert_eq!(ascii.layout, AsciiLayout::Xyz);
+    assert_eq!(ascii.delimiter, Some(','));
+    assert_eq!(ascii.header, vec!["x,y,z"]);
+    assert!(!ascii.resampled);
+
+    let img = &ascii.img_data;
+    assert_eq!((img.xres, img.yres), (3, 2));
+    // Steps of 5 Angstrom per pixel
+    assert!((img.xsize - 1.5).abs() < 1e-12);
+    assert!((img.ysize - 1.0).abs() < 1e-12);
+    // First row at maximum y
+    assert_eq!(img.img_data, vec![10.0, 11.0, 12.0, 0.0, 1.0, 2.0]);
+}
+
+#[test]
+fn test_xyz_grid_resampled_size() {
+    // The same grid, resampled because the size is given
+    let options = AsciiOptions {
+        xres: Some(3),
+        yres: Some(2),
+        ..Default::default()
+    };
+    let ascii = read_ascii("tests/test_files/ascii_xyz.csv", &options).unwrap();
+    assert!(ascii.resampled);
+    let img = &ascii.img_data;
+    assert!((img.xsize - 15.0).abs() < 1e-12);
+    assert!((img.ysize - 10.0).abs() < 1e-12);
+    assert_eq!(img.img_data, vec![10.0, 11.0, 12.0, 0.0, 1.0, 2.0]);
+}
+
+#[test]
+fn test_unknown_unit() {
+    let options = AsciiOptions {
+        xy_unit: "bohr".to_string(),
+        ..Default::default()
+    };
+    let err = read_ascii("tests/test_files/ascii_xyz.csv", &options).unwrap_err();
+    assert_eq!(err.to_string(), "Unknown length unit bohr");
+
+    let out = std::env::temp_dir().join("spm-rs-test-unit.txt");
+    std::fs::write(&out, "# Width: 2 bohr\n1 2\n3 4\n").unwrap();
+    let result = read_ascii(out.to_str().unwrap(), &AsciiOptions::default());
+    std::fs::remove_file(&out).unwrap();
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_xyz_resampled() {
+    let ascii = read_ascii(
+        "tests/test_files/ascii_xyz_irregular.dat",
+        &AsciiOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(ascii.delimiter, None);
+    assert!(ascii.resampled);
+
+    let img = &ascii.img_data;
+    assert_eq!(img.img_data.len(), img.xres * img.yres);
+    // Points at the pixel centres, the size is the range plus one pixel
+    let text = std::fs::read_to_string("tests/test_files/ascii_xyz_irregular.dat").unwrap();
+    let range = |column: usize| {
+        let values: Vec<f64> = text
+            .lines()
+            .map(|l| l.split_whitespace().nth(column).unwrap().parse().unwrap())
+            .collect();
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        max - min
+    };
+    let xsize = range(0) * img.xres as f64 / (img.xres - 1) as f64;
+    let ysize = range(1) * img.yres as f64 / (img.yres - 1) as f64;
+    assert!((img.xsize - xsize).abs() < 1e-12);
+    assert!((img.ysize - ysize).abs() < 1e-12);
+    assert!(img.img_data.iter().all(|z| (0.0..=8.0).contains(z)));
+
+    // z = x + 2y increases to the right and to the top
+    let row_mean = |row: usize| {
+        img.img_data[row * img.xres..(row + 1) * img.xres]
+            .iter()
+            .sum::<f64>()
+            / img.xres as f64
+    };
+    assert!(row_mean(0) > row_mean(img.yres - 1));
+    assert!(img.img_data[img.xres - 1] > img.img_data[0]);
+
+    let options = AsciiOptions {
+        xres: Some(8),
+        yres: Some(4),
+        ..Default::default()
+    };
+    let img = read_ascii("tests/test_files/ascii_xyz_irregular.dat", &options)
+        .unwrap()
+        .img_data;
+    assert_eq!((img.xres, img.yres), (8, 4));
+    assert!(img.img_data.iter().all(|z| z.is_finite()));
+}
+
+#[test]
+fn test_invalid_options() {
+    for (xres, yres) in [(Some(0), None), (Some(4), Some(0))] {
+        let options = AsciiOptions {
+            xres,
+            yres,
+            ..Default::default()
+        };
+        assert!(read_ascii("tests/test_files/ascii_xyz_irregular.dat", &options).is_err());
+    }
+}
+
+#[test]
+fn test_xyz_sparse_large_grid() {
+    let out = std::env::temp_dir().join("spm-rs-test-sparse.xyz");
+    std::fs::write(&out, "0 0 1\n10 0 2\n0 10 3\n5 5 4\n").unwrap();
+    let options = AsciiOptions {
+        xres: Some(500),
+        yres: Some(500),
+        ..Default::default()
+    };
+    let result = read_ascii(out.to_str().unwrap(), &options);
+    std::fs::remove_file(&out).unwrap();
+
+    let img = result.unwrap().img_data;
+    assert_eq!(img.img_data.len(), 500 * 500);
+    assert!(img.img_data.iter().all(|z| (1.0..=4.0).contains(z)));
+    // Corners at the points, the first row at maximum y
+    assert_eq!(img.img_data[0], 3.0);
+    assert_eq!(img.img_data[499 * 500], 1.0);
+    assert_eq!(img.img_data[499 * 500 + 499], 2.0);
+}
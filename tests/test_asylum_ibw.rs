@@ -0,0 +1,52 @@
+use spm_rs::asylum_ibw::{parse_note, read_asylum_ibw, NoteValue};
+
+const ASYLUM_IBW: &str = "tests/test_files/asylum_3d_wave.ibw";
+
+#[test]
+fn test_parse_note() {
+    let metadata = parse_note("ScanSize: 5e-06\nScanLines: 256\nTime: 4:45:12 PM\n");
+    assert_eq!(metadata["ScanSize"], NoteValue::Float(5e-6));
+    assert_eq!(metadata["ScanLines"], NoteValue::Int(256));
+    assert_eq!(metadata["Time"], NoteValue::Text("4:45:12 PM".to_string()));
+}
+
+#[test]
+fn test_metadata() {
+    let img = read_asylum_ibw(ASYLUM_IBW).unwrap();
+    assert_eq!(img.metadata["ScanPoints"], NoteValue::Int(4));
+    assert_eq!(img.scan_rate, 1.5);
+    assert_eq!(img.set_point, 0.8);
+    assert_eq!(img.scan_angle, 90.0);
+    assert_eq!(img.imaging_mode, "AC Mode");
+}
+
+#[test]
+fn test_sizes() {
+    let img = read_asylum_ibw(ASYLUM_IBW).unwrap();
+    assert_eq!((img.xres, img.yres), (4, 3));
+    assert_eq!(img.xsize.round(), 40.0);
+    assert_eq!(img.ysize.round(), 30.0);
+    assert_eq!(img.xoffset.round(), 100.0);
+    assert_eq!(img.yoffset.round(), -200.0);
+}
+
+#[test]
+fn test_channels() {
+    let img = read_asylum_ibw(ASYLUM_IBW).unwrap();
+    let names: Vec<_> = img.channels.iter().map(|c| c.img_id.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["asylum_3d_wave_HeightTrace", "asylum_3d_wave_PhaseTrace"]
+    );
+    assert_eq!(img.channels[0].z_unit, "m");
+    assert_eq!(img.channels[1].z_unit, "deg");
+}
+
+#[test]
+fn test_channel_data() {
+    let img = read_asylum_ibw(ASYLUM_IBW).unwrap();
+    let phase = &img.channels[1];
+    // first line of the image is the last scan line
+    assert_eq!(phase.img_data[0..4], [120.0, 121.0, 122.0, 123.0]);
+    assert_eq!(phase.img_data[8..12], [100.0, 101.0, 102.0, 103.0]);
+}
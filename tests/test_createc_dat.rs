@@ -0,0 +1,54 @@
+use spm_rs::createc_dat::read_createc_dat;
+
+const CREATEC_DAT: &str = "tests/test_files/createc_image.dat";
+
+#[test]
+fn test_header() {
+    let img = read_createc_dat(CREATEC_DAT).unwrap();
+    assert_eq!((img.xres, img.yres), (4, 2));
+    assert_eq!((img.xsize, img.ysize), (20.0, 10.0));
+    assert_eq!(img.bias, -500.0);
+    assert_eq!(img.current, 1.5e-10);
+    assert_eq!(img.header["OffsetX"], "12.5");
+    assert_eq!(img.header["Titel"], "test");
+}
+
+#[test]
+fn test_channels() {
+    let img = read_createc_dat(CREATEC_DAT).unwrap();
+    let ids: Vec<_> = img.channels.iter().map(|c| c.img_id.as_str()).collect();
+    assert_eq!(
+        ids,
+        vec![
+            "createc_image_Topography_fwd",
+            "createc_image_Current_fwd",
+            "createc_image_Topography_bwd",
+            "createc_image_Current_bwd"
+        ]
+    );
+    let units: Vec<_> = img.channels.iter().map(|c| c.z_unit.as_str()).collect();
+    assert_eq!(units, vec!["m", "A", "m", "A"]);
+}
+
+#[test]
+fn test_calibration() {
+    let img = read_createc_dat(CREATEC_DAT).unwrap();
+    // Dacto[A]z * GainZ = 5 Angstrom per DAC unit
+    let topo = &img.channels[0].img_data;
+    assert!((topo[3] - 3.0 * 5e-10).abs() < 1e-20);
+
+    // 10 V / 2^15 per ADC unit and a gain of 10^9 V/A
+    let current = &img.channels[1].img_data;
+    assert!((current[0] - 100.0 * 10.0 / 32768.0 * 1e-9).abs() < 1e-20);
+
+    // Backward lines are mirrored
+    let topo_bwd: Vec<_> = img.channels[2]
+        .img_data
+        .iter()
+        .map(|x| (x / 5e-10).round())
+        .collect();
+    assert_eq!(
+        topo_bwd,
+        vec![203.0, 202.0, 201.0, 200.0, 207.0, 206.0, 205.0, 204.0]
+    );
+}
@@ -0,0 +1,49 @@
+use spm_rs::createc_vert::read_createc_vert;
+
+const CREATEC_VERT: &str = "tests/test_files/createc_spectrum.VERT";
+
+#[test]
+fn test_header() {
+    let vert = read_createc_vert(CREATEC_VERT).unwrap();
+    assert_eq!(vert.header["Titel"], "spectrum");
+    assert_eq!(vert.num_points, 4);
+}
+
+#[test]
+fn test_position() {
+    let vert = read_createc_vert(CREATEC_VERT).unwrap();
+    assert_eq!((vert.xpos_dac, vert.ypos_dac), (100.0, -200.0));
+    // Dacto[A]xy * GainX = 2.5 Angstrom per DAC unit
+    assert_eq!(vert.spectrum.xpos, 25.0);
+    assert_eq!(vert.spectrum.ypos, -50.0);
+}
+
+#[test]
+fn test_columns() {
+    let vert = read_createc_vert(CREATEC_VERT).unwrap();
+    let spectrum = &vert.spectrum;
+    assert_eq!(spectrum.spec_id, "createc_spectrum");
+    assert_eq!(
+        (spectrum.x_label.as_str(), spectrum.x_unit.as_str()),
+        ("Bias", "mV")
+    );
+    assert_eq!(spectrum.x_data, vec![-1000.0, -500.0, 0.0, 500.0]);
+
+    let names: Vec<_> = spectrum.channels.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["Z", "Current", "ADC1"]);
+    let units: Vec<_> = spectrum.channels.iter().map(|c| c.unit.as_str()).collect();
+    assert_eq!(units, vec!["m", "A", "V"]);
+}
+
+#[test]
+fn test_calibration() {
+    let vert = read_createc_vert(CREATEC_VERT).unwrap();
+    let z = &vert.spectrum.channel("Z").unwrap().data;
+    assert!((z[1] - 2.0 * 5e-10).abs() < 1e-20);
+    let current = &vert.spectrum.channel("Current").unwrap().data;
+    assert!((current[0] - 1e-9).abs() < 1e-20);
+    assert_eq!(
+        vert.spectrum.channel("ADC1").unwrap().data,
+        vec![5.0, 0.0, -5.0, 10.0]
+    );
+}
@@ -0,0 +1,70 @@
+use spm_rs::gsf::{read_gsf, write_gsf};
+use spm_rs::mulfile::read_mul;
+
+const GSF: &str = "tests/test_files/gsf_image.gsf";
+
+#[test]
+fn test_read_gsf() {
+    let gsf = read_gsf(GSF).unwrap();
+    assert_eq!(gsf.title, "Topography");
+    assert_eq!(gsf.header["Comment"], "synthetic");
+    assert_eq!((gsf.xoffset.round(), gsf.yoffset.round()), (5.0, -1.0));
+
+    let img = &gsf.img_data;
+    assert_eq!(img.img_id, "Topography");
+    assert_eq!((img.xres, img.yres), (3, 2));
+    assert_eq!((img.xsize.round(), img.ysize.round()), (10.0, 20.0));
+    assert_eq!(img.z_unit, "m");
+    assert_eq!(img.img_data[5], f64::from(5e-10_f32));
+}
+
+#[test]
+fn test_write_gsf() {
+    let mulfile = read_mul("tests/test_files/stm-aarhus-mul-a.mul").unwrap();
+    let img = &mulfile[0].img_data;
+
+    let out = std::env::temp_dir().join("spm-rs-test-write.gsf");
+    write_gsf(out.to_str().unwrap(), img).unwrap();
+    let bytes = std::fs::read(&out).unwrap();
+    let gsf = read_gsf(out.to_str().unwrap()).unwrap();
+
+    // Header padded to a multiple of 4 bytes
+    assert_eq!((bytes.len() - img.img_data.len() * 4) % 4, 0);
+
+    let read = &gsf.img_data;
+    assert_eq!(read.img_id, img.img_id);
+    assert_eq!((read.xres, read.yres), (img.xres, img.yres));
+    assert!((read.xsize - img.xsize).abs() < 1e-9);
+    assert!((read.ysize - img.ysize).abs() < 1e-9);
+    // Values in m instead of nm
+    assert_eq!(img.z_unit, "nm");
+    assert_eq!(read.z_unit, "m");
+    for (r, x) in read.img_data.iter().zip(img.img_data.iter()) {
+        assert_eq!(*r, f64::from((x * 1e-9) as f32));
+    }
+
+    // Writing the read image again keeps the data unchanged
+    write_gsf(out.to_str().unwrap(), read).unwrap();
+    let rewritten = std::fs::read(&out).unwrap();
+    let data_len = img.img_data.len() * 4;
+    assert_eq!(
+        rewritten[rewritten.len() - data_len..],
+        bytes[bytes.len() - data_len..]
+    );
+    std::fs::remove_file(&out).unwrap();
+}
+
+#[test]
+fn test_unknown_xy_unit() {
+    let bytes = std::fs::read(GSF).unwrap();
+    let text = String::from_utf8_lossy(&bytes);
+    let pos = text.find("XYUnits = m").unwrap();
+    let mut modified = bytes.clone();
+    modified[pos..pos + 11].copy_from_slice(b"XYUnits =px");
+
+    let out = std::env::temp_dir().join("spm-rs-test-unit.gsf");
+    std::fs::write(&out, &modified).unwrap();
+    let err = read_gsf(out.to_str().unwrap()).unwrap_err();
+    std::fs::remove_file(&out).unwrap();
+    assert_eq!(err.to_string(), "Unknown XYUnits px");
+}
@@ -0,0 +1,115 @@
+use spm_rs::gwyddion::{read_gwy, write_gwy, GwyChannel, GwyValue};
+use spm_rs::mulfile::read_mul;
+
+const GWY: &str = "tests/test_files/gwyddion.gwy";
+
+#[test]
+fn test_container() {
+    let gwy = read_gwy(GWY).unwrap();
+    assert_eq!(gwy.container.name, "GwyContainer");
+    assert_eq!(
+        gwy.container.get("/0/data/visible"),
+        Some(&GwyValue::Bool(true))
+    );
+}
+
+#[test]
+fn test_channels() {
+    let gwy = read_gwy(GWY).unwrap();
+    assert_eq!(gwy.channel_nums, vec![0, 1]);
+    let ids: Vec<_> = gwy.channels.iter().map(|c| c.img_id.as_str()).collect();
+    assert_eq!(ids, vec!["gwyddion_Topography", "gwyddion_Current"]);
+
+    let topo = &gwy.channels[0];
+    assert_eq!((topo.xres, topo.yres), (3, 2));
+    assert_eq!((topo.xsize.round(), topo.ysize.round()), (10.0, 20.0));
+    assert_eq!(topo.z_unit, "m");
+    assert_eq!(topo.img_data[5], 5e-10);
+    assert_eq!(gwy.channels[1].z_unit, "A");
+}
+
+#[test]
+fn test_masks() {
+    let gwy = read_gwy(GWY).unwrap();
+    assert_eq!(gwy.masks.len(), 1);
+    assert_eq!(gwy.masks[0].channel, 0);
+    assert_eq!(
+        gwy.masks[0].data,
+        vec![false, true, false, false, true, true]
+    );
+}
+
+#[test]
+fn test_metadata() {
+    let gwy = read_gwy(GWY).unwrap();
+    assert_eq!(gwy.metadata[&0]["Bias"], "0.5 V");
+    assert_eq!(gwy.metadata[&0]["Current"], "100 pA");
+}
+
+#[test]
+fn test_graphs() {
+    let gwy = read_gwy(GWY).unwrap();
+    assert_eq!(gwy.graphs.len(), 1);
+    let graph = &gwy.graphs[0];
+    assert_eq!(graph.title, "Profiles");
+    assert_eq!(
+        (graph.x_label.as_str(), graph.x_unit.as_str()),
+        ("Distance", "m")
+    );
+    assert_eq!(graph.curves[0].description, "profile 1");
+    assert_eq!(graph.curves[0].y_data, vec![1.0, 4.0, 9.0]);
+}
+
+#[test]
+fn test_truncated() {
+    let bytes = std::fs::read(GWY).unwrap();
+    let out = std::env::temp_dir().join("spm-rs-test-truncated.gwy");
+    std::fs::write(&out, &bytes[..bytes.len() - 20]).unwrap();
+    let result = read_gwy(out.to_str().unwrap());
+    std::fs::remove_file(&out).unwrap();
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_write_gwy() {
+    let mulfile = read_mul("tests/test_files/stm-aarhus-mul-a.mul").unwrap();
+    let mut channels: Vec<GwyChannel> = mulfile.iter().take(2).map(GwyChannel::from).collect();
+    let num_pixels = mulfile[1].img_data.img_data.len();
+    channels[1].mask = Some((0..num_pixels).map(|i| i % 3 == 0).collect());
+
+    let out = std::env::temp_dir().join("spm-rs-test-write.gwy");
+    write_gwy(out.to_str().unwrap(), &channels).unwrap();
+    let gwy = read_gwy(out.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&out).unwrap();
+
+    assert_eq!(gwy.channel_nums, vec![0, 1]);
+    for (read, img) in gwy.channels.iter().zip(mulfile.iter()) {
+        assert_eq!((read.xres, read.yres), (img.xres, img.yres));
+        assert!((read.xsize - img.xsize).abs() < 1e-9);
+        assert!((read.ysize - img.ysize).abs() < 1e-9);
+        // Heights are stored in m
+        assert_eq!(read.z_unit, "m");
+        assert!((read.img_data[10] * 1e9 - img.img_data.img_data[10]).abs() < 1e-9);
+    }
+    assert_eq!(
+        gwy.container.get("/1/data/title"),
+        Some(&GwyValue::String(mulfile[1].img_id.clone()))
+    );
+
+    let field = match gwy.container.get("/1/data") {
+        Some(GwyValue::Object(field)) => field,
+        _ => panic!("No data field"),
+    };
+    assert_eq!(
+        field.get("xoff"),
+        Some(&GwyValue::Double(mulfile[1].xoffset * 1e-9))
+    );
+
+    assert_eq!(gwy.metadata[&0]["Bias"], format!("{} mV", mulfile[0].bias));
+    assert_eq!(gwy.metadata[&0]["Date"], mulfile[0].datetime.to_string());
+    assert!(gwy.metadata[&1]["Source file"].ends_with("stm-aarhus-mul-a.mul"));
+
+    assert_eq!(gwy.masks.len(), 1);
+    assert_eq!(gwy.masks[0].channel, 1);
+    assert_eq!(gwy.masks[0].data, channels[1].mask.clone().unwrap());
+}
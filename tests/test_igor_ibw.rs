@@ -84,3 +84,60 @@ fn test_dim_labels() {
         )
     );
 }
+
+const IBW_3D_WAVE: &str = "tests/test_files/test_3d_wave.ibw";
+const IBW_4D_WAVE: &str = "tests/test_files/test_4d_wave.ibw";
+
+#[test]
+fn test_data_array_matrix() {
+    let ibw = read_ibw(IBW_MATRIX).unwrap();
+    let arr = ibw.data_array().unwrap();
+    assert_eq!(arr.shape(), &[4, 4]);
+    assert_eq!(arr[[0, 0]], 1.);
+    assert_eq!(arr[[0, 1]], 2.);
+    assert_eq!(arr[[1, 0]], 5.);
+    assert_eq!(arr[[3, 3]], 16.);
+}
+
+#[test]
+fn test_data_array_3d() {
+    let ibw = read_ibw(IBW_3D_WAVE).unwrap();
+    let arr = ibw.data_array().unwrap();
+    assert_eq!(arr.shape(), &[3, 3, 3]);
+    assert_eq!(arr[[0, 1, 0]], 2.);
+    assert_eq!(arr[[1, 0, 0]], 4.);
+    assert_eq!(arr[[0, 0, 1]], 10.);
+    assert_eq!(arr[[2, 2, 2]], 27.);
+}
+
+#[test]
+fn test_data_array_4d() {
+    let ibw = read_ibw(IBW_4D_WAVE).unwrap();
+    let arr = ibw.data_array().unwrap();
+    assert_eq!(arr.shape(), &[2, 2, 2, 2]);
+    assert_eq!(arr[[0, 1, 0, 0]], 2.);
+    assert_eq!(arr[[1, 0, 0, 0]], 3.);
+    assert_eq!(arr[[0, 0, 1, 0]], 5.);
+    assert_eq!(arr[[0, 0, 0, 1]], 9.);
+    assert_eq!(arr[[1, 1, 1, 1]], 16.);
+}
+
+#[test]
+fn test_axes() {
+    let ibw = read_ibw(IBW_MATRIX).unwrap();
+    let axes = ibw.axes();
+    assert_eq!(axes.len(), 2);
+    assert_eq!(axes[0].units, "row_units");
+    assert_eq!(axes[1].units, "col_units");
+    assert_eq!(axes[0].values, vec![0., 1., 2., 3.]);
+
+    let ibw = read_ibw(IBW_4D_WAVE).unwrap();
+    assert_eq!(ibw.axes().len(), 4);
+}
+
+#[test]
+fn test_dates() {
+    let ibw = read_ibw(IBW_MATRIX).unwrap();
+    assert_eq!(ibw.creation_date.to_string(), "2023-01-22 19:23:01 UTC");
+    assert_eq!(ibw.mod_date.to_string(), "2023-01-22 19:42:21 UTC");
+}
@@ -0,0 +1,81 @@
+use spm_rs::igor_pxp::read_pxp;
+
+const PXP_FILE: &str = "tests/test_files/test_experiment.pxp";
+
+#[test]
+fn test_folder_tree() {
+    let pxp = read_pxp(PXP_FILE).unwrap();
+    assert_eq!(pxp.root.name, "root");
+    assert_eq!(pxp.root.folders.len(), 1);
+    assert_eq!(pxp.root.folders[0].name, "spectra");
+    assert_eq!(pxp.root.folders[0].folders[0].name, "sub");
+}
+
+#[test]
+fn test_waves() {
+    let pxp = read_pxp(PXP_FILE).unwrap();
+    let waves: Vec<_> = pxp
+        .waves()
+        .iter()
+        .map(|(path, wave)| format!("{}{}", path, wave.bname))
+        .collect();
+    assert_eq!(
+        waves,
+        vec![
+            "root:test_3d_wave",
+            "root:spectra:test_matrix",
+            "root:spectra:sub:test_4d_wave"
+        ]
+    );
+}
+
+#[test]
+fn test_wave_data() {
+    let pxp = read_pxp(PXP_FILE).unwrap();
+    let matrix = &pxp.root.folders[0].waves[0];
+    assert_eq!(matrix.note, "test matrix 4x4");
+    assert_eq!(matrix.data_array().unwrap().shape(), &[4, 4]);
+}
+
+#[test]
+fn test_history_and_procedure() {
+    let pxp = read_pxp(PXP_FILE).unwrap();
+    assert_eq!(pxp.history, "NewDataFolder spectra\nDisplay test_matrix\n");
+    assert!(pxp.procedure.starts_with("#pragma rtGlobals=3\n"));
+    assert_eq!(pxp.recreation, "// Platform=Windows\n");
+}
+
+#[test]
+fn test_unsupported_waves() {
+    let pxp = read_pxp("tests/test_files/text_waves.pxp").unwrap();
+    assert_eq!(
+        pxp.unsupported_waves,
+        vec!["root:labels", "root:spectra:cplx"]
+    );
+    let waves: Vec<_> = pxp
+        .waves()
+        .iter()
+        .map(|(path, wave)| format!("{}{}", path, wave.bname))
+        .collect();
+    assert_eq!(waves, vec!["root:spectra:test_matrix"]);
+}
+
+#[test]
+fn test_broken_records() {
+    let mut bytes = std::fs::read(PXP_FILE).unwrap();
+    let out = std::env::temp_dir().join("spm-rs-test-broken.pxp");
+
+    // Negative length of the first record
+    bytes[4..8].copy_from_slice(&(-8_i32).to_le_bytes());
+    std::fs::write(&out, &bytes).unwrap();
+    assert!(read_pxp(out.to_str().unwrap()).is_err());
+
+    // Wave record shortened, but within the file
+    let mut bytes = std::fs::read(PXP_FILE).unwrap();
+    let wave_record = 8 + 8 + 8 + 42;
+    bytes[wave_record + 4..wave_record + 8].copy_from_slice(&200_i32.to_le_bytes());
+    std::fs::write(&out, &bytes).unwrap();
+    let err = read_pxp(out.to_str().unwrap()).unwrap_err();
+    std::fs::remove_file(&out).unwrap();
+    assert!(err.to_string().starts_with("Wave is truncated"));
+}
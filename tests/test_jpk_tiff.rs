@@ -0,0 +1,72 @@
+use spm_rs::jpk_tiff::read_jpk_tiff;
+
+const JPK: &str = "tests/test_files/jpk.jpk";
+
+#[test]
+fn test_scan_parameters() {
+    let jpk = read_jpk_tiff(JPK).unwrap();
+    assert_eq!(jpk.start_date, "2022-05-06 07:08:09");
+    assert_eq!((jpk.xres, jpk.yres), (4, 3));
+    assert!((jpk.xsize - 2000.0).abs() < 1e-9);
+    assert!((jpk.ysize - 1500.0).abs() < 1e-9);
+    assert!((jpk.xoffset - 100.0).abs() < 1e-9);
+    assert!((jpk.yoffset + 200.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_channels() {
+    let jpk = read_jpk_tiff(JPK).unwrap();
+    let ids: Vec<_> = jpk
+        .channels
+        .iter()
+        .map(|c| c.img_data.img_id.as_str())
+        .collect();
+    assert_eq!(
+        ids,
+        vec!["jpk_height_fwd", "jpk_height_bwd", "jpk_vDeflection_fwd"]
+    );
+    assert_eq!(jpk.channels[0].fancy_name, "Height");
+    assert!(jpk.channels[1].retrace);
+
+    let slots: Vec<_> = jpk.channels[0]
+        .slots
+        .iter()
+        .map(|s| s.name.as_str())
+        .collect();
+    assert_eq!(slots, vec!["volts", "nominal"]);
+    assert_eq!(jpk.channels[0].slots[1].parent.as_deref(), Some("volts"));
+}
+
+#[test]
+fn test_calibration() {
+    let jpk = read_jpk_tiff(JPK).unwrap();
+
+    // raw -> volts -> nominal
+    let height = &jpk.channels[0].img_data;
+    assert_eq!((height.xres, height.yres), (4, 3));
+    assert_eq!(height.z_unit, "m");
+    let expected = 5000.0 * 1e-4 * 2e-6 + 1e-7;
+    assert!((height.img_data[5] - expected).abs() < 1e-15);
+
+    let deflection = &jpk.channels[2].img_data;
+    assert_eq!(deflection.z_unit, "V");
+    assert_eq!(deflection.img_data[5], 1.25);
+}
+
+#[test]
+fn test_invalid_number_of_slots() {
+    let mut bytes = std::fs::read(JPK).unwrap();
+    // NrOfSlots of the first channel, a short stored in its IFD entry
+    let entry = [0x80, 0x80, 3, 0, 1, 0, 0, 0];
+    let pos = bytes.windows(8).position(|w| w == entry).unwrap() + 8;
+    bytes[pos..pos + 2].copy_from_slice(&1000_u16.to_le_bytes());
+
+    let out = std::env::temp_dir().join("spm-rs-test-slots.jpk");
+    std::fs::write(&out, &bytes).unwrap();
+    let err = read_jpk_tiff(out.to_str().unwrap()).unwrap_err();
+    std::fs::remove_file(&out).unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Invalid number of slots 1000 of channel height"
+    );
+}
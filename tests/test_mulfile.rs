@@ -1,6 +1,9 @@
-use spm_rs::mulfile::read_mul;
+use spm_rs::mulfile::{
+    read_mul, read_mul_image, read_mul_index, read_mul_partial, write_mul, MulError,
+};
 
 const MULFILE: &str = "tests/test_files/stm-aarhus-mul-a.mul";
+const POINT_SCANS: &str = "tests/test_files/mul_point_scans.mul";
 
 #[test]
 fn test_current() {
@@ -27,12 +30,274 @@ fn test_gain() {
 fn test_scan_duration() {
     let mulfile = read_mul(MULFILE).unwrap();
     let scan_durations: Vec<_> = mulfile.iter().map(|x| x.speed.round()).collect();
-    assert_eq!(scan_durations, vec![77.0, 82.0, 123.0, 129.0]);
+    assert_eq!(scan_durations, vec![77.0, 83.0, 123.0, 129.0]);
 }
 
 #[test]
 fn test_lines_time() {
     let mulfile = read_mul(MULFILE).unwrap();
     let line_times: Vec<_> = mulfile.iter().map(|x| x.line_time.round()).collect();
-    assert_eq!(line_times, vec![150.0, 160.0, 240.0, 252.0]);
+    assert_eq!(line_times, vec![151.0, 162.0, 240.0, 253.0]);
+}
+
+#[test]
+fn test_point_scans() {
+    let mulfile = read_mul(MULFILE).unwrap();
+    assert!(mulfile.iter().all(|x| x.point_scans.is_empty()));
+}
+
+#[test]
+fn test_point_scan_signals() {
+    let mulfile = read_mul(POINT_SCANS).unwrap();
+    let point_scans = &mulfile[0].point_scans;
+    assert_eq!(point_scans.len(), 2);
+
+    let iv = &point_scans[0].spectrum;
+    assert_eq!(iv.spec_id, "mul_point_scans_1_ps1");
+    assert_eq!((iv.x_label.as_str(), iv.x_unit.as_str()), ("Bias", "mV"));
+    assert_eq!((iv.xpos, iv.ypos), (1.0, 2.0));
+    // Values as stored, whatever the type of the point scan
+    assert_eq!(point_scans[0].type_, 1);
+    let signal = iv.channel("Signal").unwrap();
+    assert_eq!(signal.unit, "");
+    assert_eq!(signal.data, vec![100.0, 200.0, -100.0, 0.0]);
+
+    let zt = &point_scans[1].spectrum;
+    assert_eq!((zt.x_label.as_str(), zt.x_unit.as_str()), ("Time", "s"));
+    assert_eq!(point_scans[1].type_, 2);
+    let signal = zt.channel("Signal").unwrap();
+    assert_eq!(signal.data, vec![1360.0, 0.0, -1360.0]);
+
+    let out = std::env::temp_dir().join("spm-rs-test-point-scans.mul");
+    write_mul(out.to_str().unwrap(), &mulfile).unwrap();
+    let written = std::fs::read(&out).unwrap();
+    std::fs::remove_file(&out).unwrap();
+    assert!(written == std::fs::read(POINT_SCANS).unwrap());
+}
+
+#[test]
+fn test_sizes() {
+    let mulfile = read_mul(MULFILE).unwrap();
+    let offsets: Vec<_> = mulfile.iter().map(|x| (x.xoffset, x.yoffset)).collect();
+    assert_eq!(
+        offsets,
+        vec![(0.0, 0.0), (28.2, 636.7), (-74.3, 803.8), (-673.2, 580.3)]
+    );
+    assert!(mulfile.iter().all(|x| x.xsize == 100.0 && x.ysize == 100.0));
+}
+
+#[test]
+fn test_mode_and_post_processing() {
+    let mulfile = read_mul(MULFILE).unwrap();
+    assert!(mulfile.iter().all(|x| x.mode == 0));
+    assert!(mulfile.iter().all(|x| x.postpr == 3));
+}
+
+#[test]
+fn test_write_mul_roundtrip() {
+    let mulfile = read_mul(MULFILE).unwrap();
+    let out = std::env::temp_dir().join("spm-rs-test-roundtrip.mul");
+    write_mul(out.to_str().unwrap(), &mulfile).unwrap();
+
+    let written = std::fs::read(&out).unwrap();
+    std::fs::remove_file(&out).unwrap();
+    assert!(written == std::fs::read(MULFILE).unwrap());
+}
+
+#[test]
+fn test_read_mul_index() {
+    let mulfile = read_mul(MULFILE).unwrap();
+    let (index, err) = read_mul_index(MULFILE).unwrap();
+    assert_eq!(err, None);
+
+    let img_nums: Vec<_> = index.iter().map(|x| x.img_num).collect();
+    assert_eq!(img_nums, vec![1, 2, 3, 4]);
+    let blocks: Vec<_> = index.iter().map(|x| x.block).collect();
+    assert_eq!(blocks, vec![3, 4100, 8197, 12294]);
+
+    for (entry, img) in index.iter().zip(mulfile.iter()) {
+        assert_eq!(entry.img_id, img.img_id);
+        assert_eq!(entry.datetime, img.datetime);
+        assert_eq!(entry.size, img.size);
+        assert_eq!((entry.xres, entry.yres), (img.xres, img.yres));
+        assert_eq!((entry.xsize, entry.ysize), (img.xsize, img.ysize));
+        assert_eq!(entry.bias, img.bias);
+        assert_eq!(entry.current, img.current);
+    }
+}
+
+#[test]
+fn test_read_mul_image() {
+    let mulfile = read_mul(MULFILE).unwrap();
+    let img = read_mul_image(MULFILE, 3).unwrap();
+    assert_eq!(img.img_id, mulfile[2].img_id);
+    assert_eq!(img.img_data.img_data, mulfile[2].img_data.img_data);
+    assert_eq!(img.point_scans.len(), mulfile[2].point_scans.len());
+
+    assert!(read_mul_image(MULFILE, 5).is_err());
+}
+
+fn read_modified_mul(name: &str, bytes: &[u8]) -> (Vec<usize>, Option<MulError>) {
+    let out = std::env::temp_dir().join(name);
+    std::fs::write(&out, bytes).unwrap();
+    let (mulfile, err) = read_mul_partial(out.to_str().unwrap()).unwrap();
+    assert!(read_mul(out.to_str().unwrap()).is_err());
+    std::fs::remove_file(&out).unwrap();
+    (mulfile.iter().map(|x| x.img_num as usize).collect(), err)
+}
+
+#[test]
+fn test_truncated_image() {
+    let bytes = std::fs::read(MULFILE).unwrap();
+    let (img_nums, err) =
+        read_modified_mul("spm-rs-test-trunc-img.mul", &bytes[..8197 * 128 + 1000]);
+    assert_eq!(img_nums, vec![1, 2]);
+    assert_eq!(
+        err,
+        Some(MulError::TruncatedImage {
+            img_num: 3,
+            block: 8197,
+            expected_blocks: 4097,
+            available_blocks: 7
+        })
+    );
+}
+
+#[test]
+fn test_truncated_header() {
+    let bytes = std::fs::read(MULFILE).unwrap();
+    let (img_nums, err) =
+        read_modified_mul("spm-rs-test-trunc-hdr.mul", &bytes[..12294 * 128 + 50]);
+    assert_eq!(img_nums, vec![1, 2, 3]);
+    assert_eq!(
+        err,
+        Some(MulError::TruncatedHeader {
+            block: 12294,
+            num_bytes: 50
+        })
+    );
+}
+
+#[test]
+fn test_invalid_datetime() {
+    let mut bytes = std::fs::read(MULFILE).unwrap();
+    // Month of the second image
+    bytes[4100 * 128 + 12] = 13;
+    let (img_nums, err) = read_modified_mul("spm-rs-test-invalid-date.mul", &bytes);
+    assert_eq!(img_nums, vec![1]);
+    assert_eq!(
+        err,
+        Some(MulError::InvalidDatetime {
+            img_num: 2,
+            block: 4100
+        })
+    );
+}
+
+#[test]
+fn test_invalid_resolution() {
+    let mut bytes = std::fs::read(MULFILE).unwrap();
+    // xres of the third image
+    bytes[8197 * 128 + 4..8197 * 128 + 6].copy_from_slice(&(-256_i16).to_le_bytes());
+    let (img_nums, err) = read_modified_mul("spm-rs-test-invalid-res.mul", &bytes);
+    assert_eq!(img_nums, vec![1, 2]);
+    assert_eq!(
+        err,
+        Some(MulError::InvalidResolution {
+            img_num: 3,
+            block: 8197,
+            xres: -256,
+            yres: 512
+        })
+    );
+}
+
+fn read_modified_mul_index(name: &str, bytes: &[u8]) -> (Vec<i16>, Option<MulError>) {
+    let out = std::env::temp_dir().join(name);
+    std::fs::write(&out, bytes).unwrap();
+    let (index, err) = read_mul_index(out.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&out).unwrap();
+    (index.iter().map(|x| x.img_num).collect(), err)
+}
+
+#[test]
+fn test_read_mul_index_truncated() {
+    let bytes = std::fs::read(MULFILE).unwrap();
+    let (img_nums, err) =
+        read_modified_mul_index("spm-rs-test-index-hdr.mul", &bytes[..12294 * 128 + 50]);
+    assert_eq!(img_nums, vec![1, 2, 3]);
+    assert_eq!(
+        err,
+        Some(MulError::TruncatedHeader {
+            block: 12294,
+            num_bytes: 50
+        })
+    );
+
+    let (img_nums, err) =
+        read_modified_mul_index("spm-rs-test-index-img.mul", &bytes[..8197 * 128 + 1000]);
+    assert_eq!(img_nums, vec![1, 2]);
+    assert!(matches!(
+        err,
+        Some(MulError::TruncatedImage { img_num: 3, .. })
+    ));
+}
+
+#[test]
+fn test_read_mul_index_corrupt_header() {
+    let mut bytes = std::fs::read(MULFILE).unwrap();
+    // Month of the second image
+    bytes[4100 * 128 + 12] = 13;
+    let (img_nums, err) = read_modified_mul_index("spm-rs-test-index-date.mul", &bytes);
+    assert_eq!(img_nums, vec![1]);
+    assert_eq!(
+        err,
+        Some(MulError::InvalidDatetime {
+            img_num: 2,
+            block: 4100
+        })
+    );
+
+    // Size of the second image
+    let mut bytes = std::fs::read(MULFILE).unwrap();
+    bytes[4100 * 128 + 2..4100 * 128 + 4].copy_from_slice(&0_i16.to_le_bytes());
+    let (img_nums, err) = read_modified_mul_index("spm-rs-test-index-size.mul", &bytes);
+    assert_eq!(img_nums, vec![1]);
+    assert_eq!(
+        err,
+        Some(MulError::InvalidSize {
+            img_num: 2,
+            block: 4100,
+            size: 0
+        })
+    );
+}
+
+fn write_mul_error(images: &[spm_rs::mulfile::MulImage]) -> String {
+    let out = std::env::temp_dir().join("spm-rs-test-write-error.mul");
+    let err = write_mul(out.to_str().unwrap(), images).unwrap_err();
+    assert!(!out.exists());
+    err.to_string()
+}
+
+#[test]
+fn test_write_mul_out_of_range() {
+    let mut mulfile = read_mul(POINT_SCANS).unwrap();
+    mulfile[0].xsize = 4000.0;
+    assert!(write_mul_error(&mulfile).starts_with("Size of image 1"));
+
+    let mut mulfile = read_mul(POINT_SCANS).unwrap();
+    mulfile[0].img_data.img_data[5] *= 1e6;
+    assert!(write_mul_error(&mulfile).starts_with("Pixel of image 1"));
+
+    let mut mulfile = read_mul(POINT_SCANS).unwrap();
+    mulfile[0].zscale = 0;
+    assert_eq!(write_mul_error(&mulfile), "Image 1 has a z scale of 0");
+
+    let mut mulfile = read_mul(POINT_SCANS).unwrap();
+    mulfile[0].point_scans[1].spectrum.x_data.pop();
+    assert_eq!(
+        write_mul_error(&mulfile),
+        "Point scan mul_point_scans_1_ps2 of image 1 has 3 data points for 2 x values"
+    );
 }
@@ -0,0 +1,143 @@
+use spm_rs::nanonis_3ds::read_3ds;
+
+const GRID: &str = "tests/test_files/grid_spectroscopy.3ds";
+
+#[test]
+fn test_header() {
+    let grid = read_3ds(GRID).unwrap();
+    assert_eq!(grid.experiment, "Grid Spectroscopy");
+    assert_eq!(
+        grid.start_time.unwrap().to_string(),
+        "2021-03-12 14:25:01 UTC"
+    );
+    assert_eq!(grid.comment, "dI/dV map");
+    assert_eq!(grid.header["Filetype"], "Linear");
+    assert_eq!((grid.xres, grid.yres), (3, 2));
+    assert_eq!((grid.xcenter.round(), grid.ycenter.round()), (1.0, 2.0));
+    assert_eq!((grid.xsize.round(), grid.ysize.round()), (3.0, 2.0));
+}
+
+#[test]
+fn test_sweep() {
+    let grid = read_3ds(GRID).unwrap();
+    assert_eq!(grid.sweep_label, "Bias");
+    assert_eq!(grid.sweep_unit, "V");
+    assert_eq!(grid.sweep, vec![-1.0, -0.5, 0.0, 0.5]);
+}
+
+#[test]
+fn test_params() {
+    let grid = read_3ds(GRID).unwrap();
+    assert_eq!(
+        grid.param_names,
+        vec!["Sweep Start", "Sweep End", "X (m)", "Y (m)", "Z (m)"]
+    );
+    assert_eq!(grid.params.shape(), &[2, 3, 5]);
+    let z = grid.param("Z (m)").unwrap();
+    assert!((z[[1, 2]] - 5e-10).abs() < 1e-15);
+}
+
+#[test]
+fn test_channels() {
+    let grid = read_3ds(GRID).unwrap();
+    let names: Vec<_> = grid.channels.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["Current", "LI Demod 1 X"]);
+    let current = grid.channel("Current").unwrap();
+    assert_eq!(current.unit, "A");
+    assert_eq!(current.data.shape(), &[2, 3, 4]);
+    assert_eq!(current.data[[1, 0, 2]], 32.0);
+    assert_eq!(grid.channel("LI Demod 1 X").unwrap().data[[0, 1, 3]], -13.0);
+}
+
+#[test]
+fn test_spectrum() {
+    let grid = read_3ds(GRID).unwrap();
+    let spectrum = grid.spectrum(2, 1).unwrap();
+    assert_eq!(spectrum.spec_id, "grid_spectroscopy_2_1");
+    assert_eq!((spectrum.xpos.round(), spectrum.ypos.round()), (1.0, 1.0));
+    assert_eq!(spectrum.x_data, grid.sweep);
+    assert_eq!(
+        spectrum.channel("Current").unwrap().data,
+        vec![50.0, 51.0, 52.0, 53.0]
+    );
+    assert!(grid.spectrum(3, 0).is_none());
+}
+
+#[test]
+fn test_map() {
+    let grid = read_3ds(GRID).unwrap();
+    let map = grid.map("Current", 1).unwrap();
+    assert_eq!((map.xres, map.yres), (3, 2));
+    assert_eq!(map.img_data, vec![1.0, 11.0, 21.0, 31.0, 41.0, 51.0]);
+    assert!(grid.map("Current", 4).is_none());
+}
+
+#[test]
+fn test_data_starting_with_line_break_bytes() {
+    let mut bytes = std::fs::read(GRID).unwrap();
+    let marker = b":HEADER_END:\r\n";
+    let start = bytes
+        .windows(marker.len())
+        .position(|w| w == marker)
+        .unwrap()
+        + marker.len();
+    // X of the first point, whose first bytes equal `\r\n`
+    let x = [0x0D, 0x0A, 0x0D, 0x0A];
+    bytes[start + 8..start + 12].copy_from_slice(&x);
+    // Sweep start of the first point, starting with `\n`
+    let sweep_start = [0x0A, 0x00, 0x00, 0x01];
+    bytes[start..start + 4].copy_from_slice(&sweep_start);
+
+    let out = std::env::temp_dir().join("spm-rs-test-line-break.3ds");
+    std::fs::write(&out, &bytes).unwrap();
+    let grid = read_3ds(out.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&out).unwrap();
+
+    assert_eq!(
+        grid.params[[0, 0, 0]],
+        f64::from(f32::from_be_bytes(sweep_start))
+    );
+    assert_eq!(grid.params[[0, 0, 2]], f64::from(f32::from_be_bytes(x)));
+    assert_eq!(grid.channels[0].data[[1, 2, 3]], 53.0);
+}
+
+fn read_modified_3ds(name: &str, bytes: &[u8]) -> anyhow::Result<spm_rs::nanonis_3ds::Nanonis3ds> {
+    let out = std::env::temp_dir().join(name);
+    std::fs::write(&out, bytes).unwrap();
+    let grid = read_3ds(out.to_str().unwrap());
+    std::fs::remove_file(&out).unwrap();
+    grid
+}
+
+#[test]
+fn test_stopped_grid() {
+    let bytes = std::fs::read(GRID).unwrap();
+    // First line of 3 points with 5 parameters and 2x4 values each
+    let line_end = bytes.len() - 3 * 13 * 4;
+    let grid = read_modified_3ds("spm-rs-test-stopped.3ds", &bytes[..line_end]).unwrap();
+    assert_eq!((grid.xres, grid.yres), (3, 2));
+    assert_eq!(grid.channels[0].data[[0, 2, 3]], 23.0);
+    assert!(grid.channels[0].data[[1, 0, 0]].is_nan());
+
+    // Less than the first line
+    assert!(read_modified_3ds("spm-rs-test-stopped.3ds", &bytes[..line_end - 4]).is_err());
+}
+
+#[test]
+fn test_grid_larger_than_data() {
+    let text = std::fs::read(GRID).unwrap();
+    for (from, to) in [
+        ("Grid dim=\"3 x 2\"", "Grid dim=\"300000 x 200000\""),
+        ("Points=4", "Points=18446744073709551615"),
+    ] {
+        let pos = text
+            .windows(from.len())
+            .position(|w| w == from.as_bytes())
+            .unwrap();
+        let mut bytes = text[..pos].to_vec();
+        bytes.extend_from_slice(to.as_bytes());
+        bytes.extend_from_slice(&text[pos + from.len()..]);
+        let err = read_modified_3ds("spm-rs-test-large.3ds", &bytes).unwrap_err();
+        assert!(err.to_string().contains("is too large"));
+    }
+}
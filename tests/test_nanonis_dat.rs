@@ -0,0 +1,60 @@
+use spm_rs::nanonis_dat::{read_nanonis_dat, SweepDirection};
+
+const BIAS_SPEC: &str = "tests/test_files/bias_spectroscopy.dat";
+
+#[test]
+fn test_header() {
+    let dat = read_nanonis_dat(BIAS_SPEC).unwrap();
+    assert_eq!(dat.experiment, "bias spectroscopy");
+    assert_eq!(dat.datetime.unwrap().to_string(), "2021-03-12 14:25:01 UTC");
+    assert_eq!(dat.header["Filter type"], "Gaussian");
+    assert_eq!(dat.header["User"], "");
+    assert!(dat.backward_sweep);
+    assert_eq!(dat.num_sweeps, 2);
+}
+
+#[test]
+fn test_position() {
+    let dat = read_nanonis_dat(BIAS_SPEC).unwrap();
+    assert_eq!(dat.spectrum.xpos, 1.5);
+    assert_eq!(dat.spectrum.ypos, -2.5);
+}
+
+#[test]
+fn test_columns() {
+    let dat = read_nanonis_dat(BIAS_SPEC).unwrap();
+    let spectrum = &dat.spectrum;
+    assert_eq!(spectrum.spec_id, "bias_spectroscopy");
+    assert_eq!(
+        (spectrum.x_label.as_str(), spectrum.x_unit.as_str()),
+        ("Bias calc", "V")
+    );
+    assert_eq!(spectrum.x_data, vec![-1.0, -0.5, 0.0, 0.5, 1.0]);
+
+    let names: Vec<_> = spectrum.channels.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "Current",
+            "Current [bwd]",
+            "Current [AVG]",
+            "LI Demod 1 X [00002]"
+        ]
+    );
+    let current = spectrum.channel("Current").unwrap();
+    assert_eq!(current.unit, "A");
+    assert_eq!(current.data[3], 1e-10);
+    assert!(spectrum.channel("Current [bwd]").unwrap().data[4].is_nan());
+}
+
+#[test]
+fn test_channel_info() {
+    let dat = read_nanonis_dat(BIAS_SPEC).unwrap();
+    let info = &dat.channel_info;
+    assert_eq!(info[0].signal, "Current");
+    assert_eq!(info[0].direction, SweepDirection::Forward);
+    assert_eq!(info[1].direction, SweepDirection::Backward);
+    assert!(info[2].average);
+    assert_eq!(info[3].signal, "LI Demod 1 X");
+    assert_eq!(info[3].sweep, Some(2));
+}
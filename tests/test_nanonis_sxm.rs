@@ -0,0 +1,66 @@
+use spm_rs::nanonis_sxm::{read_sxm, SxmScanDirection};
+
+const SXM: &str = "tests/test_files/test_scan.sxm";
+
+#[test]
+fn test_sizes() {
+    let sxm = read_sxm(SXM).unwrap();
+    assert_eq!((sxm.xres, sxm.yres), (4, 3));
+    assert_eq!(sxm.xsize.round(), 20.0);
+    assert_eq!(sxm.ysize.round(), 15.0);
+    assert_eq!(sxm.xoffset.round(), 1.0);
+    assert_eq!(sxm.yoffset.round(), -2.0);
+    assert_eq!(sxm.scan_angle, 30.0);
+    assert_eq!(sxm.scan_dir, SxmScanDirection::Up);
+}
+
+#[test]
+fn test_metadata() {
+    let sxm = read_sxm(SXM).unwrap();
+    assert_eq!(sxm.datetime.to_string(), "2021-03-12 14:23:10 UTC");
+    assert_eq!(sxm.bias, 0.5);
+    assert_eq!(sxm.acq_time, 12.5);
+    assert_eq!(sxm.comment, "Test scan\nsecond line");
+    assert_eq!(sxm.z_controller["Name"], "log Current");
+    assert_eq!(sxm.z_controller["Setpoint"], "1.000E-10 A");
+    assert_eq!(sxm.header["Current>Current (A)"], "1E-10");
+    assert_eq!(sxm.header["SCAN_FILE"], "C:\\Data\\test_scan001.sxm");
+}
+
+#[test]
+fn test_data_info() {
+    let sxm = read_sxm(SXM).unwrap();
+    let names: Vec<_> = sxm.data_info.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(names, vec!["Z", "Current"]);
+    assert_eq!(sxm.data_info[0].channel, 14);
+    assert_eq!(sxm.data_info[0].direction, "both");
+    assert_eq!(sxm.data_info[0].calibration, 9e-9);
+}
+
+#[test]
+fn test_channels() {
+    let sxm = read_sxm(SXM).unwrap();
+    let ids: Vec<_> = sxm.channels.iter().map(|x| x.img_id.as_str()).collect();
+    assert_eq!(
+        ids,
+        vec![
+            "test_scan_Z_fwd",
+            "test_scan_Z_bwd",
+            "test_scan_Current_fwd"
+        ]
+    );
+    let units: Vec<_> = sxm.channels.iter().map(|x| x.z_unit.as_str()).collect();
+    assert_eq!(units, vec!["m", "m", "A"]);
+
+    // Scanned upwards, so the first line in the file is the bottom one
+    assert_eq!(
+        sxm.channels[0].img_data,
+        vec![8.0, 9.0, 10.0, 11.0, 4.0, 5.0, 6.0, 7.0, 0.0, 1.0, 2.0, 3.0]
+    );
+    // Backward lines are mirrored
+    assert_eq!(
+        sxm.channels[1].img_data,
+        vec![111.0, 110.0, 109.0, 108.0, 107.0, 106.0, 105.0, 104.0, 103.0, 102.0, 101.0, 100.0]
+    );
+    assert_eq!(sxm.channels[2].img_data[8], 200.0);
+}
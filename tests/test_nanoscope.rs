@@ -0,0 +1,74 @@
+use spm_rs::nanoscope::read_nanoscope;
+
+const NANOSCOPE: &str = "tests/test_files/nanoscope.spm";
+
+#[test]
+fn test_header() {
+    let file = read_nanoscope(NANOSCOPE).unwrap();
+    assert_eq!(file.version, "0x09200000");
+    assert_eq!(
+        file.datetime.unwrap().to_string(),
+        "2020-01-10 15:21:37 UTC"
+    );
+    let sections: Vec<_> = file.header.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(
+        sections,
+        vec![
+            "File list",
+            "Scanner list",
+            "Ciao scan list",
+            "Ciao image list",
+            "Ciao image list",
+            "Ciao image list"
+        ]
+    );
+    assert_eq!(file.section("Ciao scan list").unwrap()["Lines"], "3");
+}
+
+#[test]
+fn test_channels() {
+    let file = read_nanoscope(NANOSCOPE).unwrap();
+    let ids: Vec<_> = file
+        .channels
+        .iter()
+        .map(|c| c.img_data.img_id.as_str())
+        .collect();
+    assert_eq!(
+        ids,
+        vec![
+            "nanoscope_Height Sensor_fwd",
+            "nanoscope_Deflection Error_bwd",
+            "nanoscope_Input1_fwd"
+        ]
+    );
+
+    let height = &file.channel("Height Sensor").unwrap().img_data;
+    assert_eq!((height.xres, height.yres), (4, 3));
+    assert_eq!(
+        (height.xsize.round(), height.ysize.round()),
+        (2000.0, 1500.0)
+    );
+    assert_eq!(height.z_unit, "nm");
+}
+
+#[test]
+fn test_soft_scale() {
+    let file = read_nanoscope(NANOSCOPE).unwrap();
+
+    // 32 bit data, 0.000375 V/LSB of 16 bit and 20 nm/V
+    let height = file.channel("Height Sensor").unwrap();
+    let expected = 0.000375 / 65536.0 * 20.0;
+    assert!((height.z_scale - expected).abs() < 1e-15);
+    // First line in the file is the bottom line of the image
+    assert!((height.img_data.img_data[8] + 5000.0 * expected).abs() < 1e-12);
+
+    let deflection = file.channel("Deflection Error").unwrap();
+    assert_eq!(deflection.line_direction, "Retrace");
+    assert_eq!(deflection.img_data.z_unit, "nm");
+    assert!((deflection.img_data.img_data[0] - 3000.0 * 0.000375 * 50.0).abs() < 1e-9);
+
+    // No soft scale, stays in V
+    let input = file.channel("Input1").unwrap();
+    assert_eq!(input.img_data.z_unit, "V");
+    assert!((input.img_data.img_data[11] + 2000.0 * 0.0001).abs() < 1e-12);
+}
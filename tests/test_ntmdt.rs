@@ -0,0 +1,62 @@
+use spm_rs::ntmdt::{read_mdt, MdtData};
+
+const MDT: &str = "tests/test_files/ntmdt.mdt";
+
+#[test]
+fn test_frames() {
+    let mdt = read_mdt(MDT).unwrap();
+    let types: Vec<_> = mdt.frames.iter().map(|f| f.frame_type).collect();
+    assert_eq!(types, vec![0, 0, 1, 3]);
+    assert_eq!(
+        mdt.frames[0].datetime.unwrap().to_string(),
+        "2021-03-14 15:09:26 UTC"
+    );
+    assert!(matches!(mdt.frames[3].data, MdtData::Unsupported));
+    assert_eq!(mdt.images().count(), 2);
+    assert_eq!(mdt.spectra().count(), 1);
+}
+
+#[test]
+fn test_scanned_frame() {
+    let mdt = read_mdt(MDT).unwrap();
+    let params = mdt.frames[0].params.as_ref().unwrap();
+    assert_eq!(params.x_scale.unit, "nm");
+    assert_eq!(params.scan_dir, 1);
+    assert!((params.bias - 0.2).abs() < 1e-6);
+    assert!((params.setpoint - 1.5).abs() < 1e-6);
+
+    let topo = mdt.images().next().unwrap();
+    assert_eq!(topo.img_id, "ntmdt_0");
+    assert_eq!((topo.xres, topo.yres), (4, 3));
+    assert_eq!((topo.xsize, topo.ysize), (10.0, 15.0));
+    assert_eq!(topo.z_unit, "\u{c5}");
+    // First line in the file is the bottom line of the image, 1 + 0.1 * raw
+    assert!((topo.img_data[8] - (1.0 - 5.0)).abs() < 1e-6);
+    assert!((topo.img_data[3] - (1.0 + 6.0)).abs() < 1e-6);
+}
+
+#[test]
+fn test_scanned_frame_with_dots() {
+    let mdt = read_mdt(MDT).unwrap();
+    let current = mdt.images().nth(1).unwrap();
+    assert_eq!((current.xres, current.yres), (2, 2));
+    assert_eq!((current.xsize, current.ysize), (2000.0, 2000.0));
+    assert_eq!(current.z_unit, "nA");
+    let expected = [0.03, 0.04, 0.01, 0.02];
+    for (value, expected) in current.img_data.iter().zip(expected) {
+        assert!((value - expected).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_curve_frame() {
+    let mdt = read_mdt(MDT).unwrap();
+    let spectrum = mdt.spectra().next().unwrap();
+    assert_eq!(spectrum.spec_id, "ntmdt_2");
+    assert_eq!(spectrum.x_unit, "V");
+    assert_eq!(spectrum.x_data, vec![-1.0, -0.5, 0.0, 0.5, 1.0]);
+    let z = spectrum.channel("Z").unwrap();
+    assert_eq!(z.unit, "nA");
+    assert!((z.data[0] + 2.0).abs() < 1e-6);
+    assert!((z.data[4] - 2.0).abs() < 1e-6);
+}
@@ -0,0 +1,286 @@
+use flate2::Crc;
+use spm_rs::mulfile::read_mul;
+use spm_rs::nanonis_3ds::read_3ds;
+use spm_rs::nanonis_dat::read_nanonis_dat;
+use spm_rs::nanonis_sxm::read_sxm;
+use spm_rs::numpy::{
+    grid_arrays, image_arrays, npz_names, save_npz, spectrum_arrays, write_npy, write_npz,
+    NpyArray, NpyData, NpzExport,
+};
+
+fn u16_le(bytes: &[u8], pos: usize) -> usize {
+    u16::from_le_bytes(bytes[pos..pos + 2].try_into().unwrap()) as usize
+}
+
+fn u32_le(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap())
+}
+
+/// Header dict and data of a `.npy` file
+fn parse_npy(bytes: &[u8]) -> (String, &[u8]) {
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    let header_len = u16_le(bytes, 8);
+    assert_eq!((10 + header_len) % 64, 0);
+    let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+    assert!(header.ends_with('\n'));
+    (header.trim_end().to_string(), &bytes[10 + header_len..])
+}
+
+fn f64_data(data: &[u8]) -> Vec<f64> {
+    data.chunks_exact(8)
+        .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+        .collect()
+}
+
+/// Name and contents of the entries of a stored zip, from the central directory
+fn parse_npz(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let eocd = bytes.len() - 22;
+    assert_eq!(u32_le(bytes, eocd), 0x0605_4b50);
+    let num_entries = u16_le(bytes, eocd + 10);
+    let mut pos = u32_le(bytes, eocd + 16) as usize;
+    let mut entries = Vec::new();
+    for _ in 0..num_entries {
+        assert_eq!(u32_le(bytes, pos), 0x0201_4b50);
+        // Stored, with the UTF-8 flag for names that are not ASCII
+        assert_eq!(u16_le(bytes, pos + 10), 0);
+        let flags = u16_le(bytes, pos + 8);
+        let crc = u32_le(bytes, pos + 16);
+        let size = u32_le(bytes, pos + 20) as usize;
+        let name_len = u16_le(bytes, pos + 28);
+        let offset = u32_le(bytes, pos + 42) as usize;
+        let name = std::str::from_utf8(&bytes[pos + 46..pos + 46 + name_len]).unwrap();
+        assert_eq!(flags, if name.is_ascii() { 0 } else { 0x0800 });
+
+        assert_eq!(u32_le(bytes, offset), 0x0403_4b50);
+        assert_eq!(u32_le(bytes, offset + 14), crc);
+        assert_eq!(&bytes[offset + 30..offset + 30 + name_len], name.as_bytes());
+        let start = offset + 30 + name_len;
+        let data = bytes[start..start + size].to_vec();
+        let mut check = Crc::new();
+        check.update(&data);
+        assert_eq!(check.sum(), crc);
+
+        entries.push((name.to_string(), data));
+        pos += 46 + name_len;
+    }
+    entries
+}
+
+#[test]
+fn test_image_npy() {
+    let mulfile = read_mul("tests/test_files/stm-aarhus-mul-a.mul").unwrap();
+    let img = &mulfile[0].img_data;
+
+    let out = std::env::temp_dir().join("spm-rs-test-image.npy");
+    write_npy(out.to_str().unwrap(), &NpyArray::from(img)).unwrap();
+    let bytes = std::fs::read(&out).unwrap();
+    std::fs::remove_file(&out).unwrap();
+
+    let (header, data) = parse_npy(&bytes);
+    assert_eq!(
+        header,
+        format!(
+            "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+            img.yres, img.xres
+        )
+    );
+    assert_eq!(f64_data(data), img.img_data);
+}
+
+#[test]
+fn test_spectrum_and_volume_npy() {
+    let dat = read_nanonis_dat("tests/test_files/bias_spectroscopy.dat").unwrap();
+    let spectrum = &dat.spectrum;
+    let bytes = NpyArray::from(spectrum).to_bytes().unwrap();
+    let (header, data) = parse_npy(&bytes);
+    let n = spectrum.x_data.len();
+    assert!(header.contains(&format!(
+        "'shape': ({}, {})",
+        spectrum.channels.len() + 1,
+        n
+    )));
+    let data = f64_data(data);
+    assert_eq!(data[..n], spectrum.x_data[..]);
+    assert_eq!(data[n..2 * n], spectrum.channels[0].data[..]);
+
+    let grid = read_3ds("tests/test_files/grid_spectroscopy.3ds").unwrap();
+    let volume = &grid.channels[0].data;
+    let bytes = NpyArray::from(volume).to_bytes().unwrap();
+    let (header, data) = parse_npy(&bytes);
+    assert!(header.contains(&format!(
+        "'shape': ({}, {}, {})",
+        grid.yres,
+        grid.xres,
+        grid.sweep.len()
+    )));
+    // C order, the last index fastest
+    let data = f64_data(data);
+    assert_eq!(data[1], volume[[0, 0, 1]]);
+    assert_eq!(data[grid.sweep.len()], volume[[0, 1, 0]]);
+}
+
+#[test]
+fn test_string_npy() {
+    let array = NpyArray {
+        shape: vec![2],
+        data: NpyData::Unicode(vec!["nm".to_string(), "\u{c5}".to_string()]),
+    };
+    let bytes = array.to_bytes().unwrap();
+    let (header, data) = parse_npy(&bytes);
+    assert!(header.starts_with("{'descr': '<U2', 'fortran_order': False, 'shape': (2,), }"));
+    assert_eq!(data, b"n\0\0\0m\0\0\0\xc5\0\0\0\0\0\0\0");
+
+    let (header, _) = parse_npy(&NpyArray::string("V").to_bytes().unwrap());
+    assert!(header.contains("'shape': ()"));
+
+    assert!(NpyArray::float64(&[2, 2], vec![1.0]).to_bytes().is_err());
+}
+
+#[test]
+fn test_npz() {
+    let mulfile = read_mul("tests/test_files/stm-aarhus-mul-a.mul").unwrap();
+    let img = &mulfile[0].img_data;
+    let dat = read_nanonis_dat("tests/test_files/bias_spectroscopy.dat").unwrap();
+    let grid = read_3ds("tests/test_files/grid_spectroscopy.3ds").unwrap();
+
+    let mut arrays = image_arrays(img);
+    arrays.extend(spectrum_arrays(&dat.spectrum));
+    arrays.extend(grid_arrays(&grid));
+
+    let out = std::env::temp_dir().join("spm-rs-test.npz");
+    write_npz(out.to_str().unwrap(), &arrays).unwrap();
+    let bytes = std::fs::read(&out).unwrap();
+    std::fs::remove_file(&out).unwrap();
+
+    let entries = parse_npz(&bytes);
+    assert_eq!(entries.len(), arrays.len());
+    for ((name, data), (array_name, array)) in entries.iter().zip(arrays.iter()) {
+        assert_eq!(name, &format!("{}.npy", array_name));
+        assert_eq!(data, &array.to_bytes().unwrap());
+    }
+
+    let entry = |name: &str| {
+        &entries
+            .iter()
+            .find(|(n, _)| n == &format!("{}.npy", name))
+            .unwrap()
+            .1
+    };
+    // Axes in nm
+    let x = f64_data(parse_npy(entry(&format!("{}_x", img.img_id))).1);
+    assert_eq!(x.len(), img.xres);
+    assert_eq!(x[0], 0.0);
+    assert!((x[1] - img.xsize / img.xres as f64).abs() < 1e-12);
+    let sweep = f64_data(parse_npy(entry("sweep")).1);
+    assert_eq!(sweep, grid.sweep);
+    let (header, _) = parse_npy(entry(&grid.channels[0].name));
+    assert!(header.contains(&format!("'shape': ({}, {}, ", grid.yres, grid.xres)));
+}
+
+/// Entries of the `.npz` written for the file
+fn saved_npz<T: NpzExport + ?Sized>(name: &str, file: &T) -> Vec<(String, Vec<u8>)> {
+    let out = std::env::temp_dir().join(name);
+    save_npz(out.to_str().unwrap(), file).unwrap();
+    let bytes = std::fs::read(&out).unwrap();
+    std::fs::remove_file(&out).unwrap();
+    parse_npz(&bytes)
+}
+
+fn entry<'a>(entries: &'a [(String, Vec<u8>)], name: &str) -> (String, &'a [u8]) {
+    let (_, bytes) = entries
+        .iter()
+        .find(|(n, _)| n == &format!("{}.npy", name))
+        .unwrap_or_else(|| panic!("No entry {}", name));
+    parse_npy(bytes)
+}
+
+fn string_data(data: &[u8]) -> String {
+    data.chunks_exact(4)
+        .map(|c| char::from_u32(u32::from_le_bytes(c.try_into().unwrap())).unwrap())
+        .filter(|&c| c != '\0')
+        .collect()
+}
+
+#[test]
+fn test_save_npz_mul() {
+    let mulfile = read_mul("tests/test_files/mul_point_scans.mul").unwrap();
+    let entries = saved_npz("spm-rs-test-mul.npz", &mulfile);
+
+    let (header, _) = entry(&entries, "mul_point_scans_1");
+    assert!(header.contains("'shape': (8, 8)"));
+    let (_, data) = entry(&entries, "mul_point_scans_1_ps1_Signal");
+    assert_eq!(f64_data(data), vec![100.0, 200.0, -100.0, 0.0]);
+
+    let (header, data) = entry(&entries, "mul_point_scans_1_bias");
+    assert!(header.contains("'shape': ()"));
+    assert_eq!(f64_data(data), vec![mulfile[0].bias]);
+    let (_, data) = entry(&entries, "mul_point_scans_1_current");
+    assert_eq!(f64_data(data), vec![mulfile[0].current]);
+    let (_, data) = entry(&entries, "mul_point_scans_1_datetime");
+    assert_eq!(string_data(data), "2023-05-17T10:30:00");
+    let (_, data) = entry(&entries, "mul_point_scans_1_title");
+    assert_eq!(string_data(data), "point scans");
+}
+
+#[test]
+fn test_save_npz_nanonis() {
+    let sxm = read_sxm("tests/test_files/test_scan.sxm").unwrap();
+    let entries = saved_npz("spm-rs-test-sxm.npz", &sxm);
+    for channel in sxm.channels.iter() {
+        entry(&entries, &channel.img_id);
+    }
+    let (_, data) = entry(&entries, "bias");
+    assert_eq!(f64_data(data), vec![sxm.bias]);
+    let (header, _) = entry(&entries, "header_keys");
+    assert!(header.contains(&format!("'shape': ({},)", sxm.header.len())));
+
+    let dat = read_nanonis_dat("tests/test_files/bias_spectroscopy.dat").unwrap();
+    let entries = saved_npz("spm-rs-test-dat.npz", &dat);
+    entry(&entries, &format!("{}_x", dat.spectrum.spec_id));
+    let (_, data) = entry(&entries, "experiment");
+    assert_eq!(string_data(data), dat.experiment);
+
+    let grid = read_3ds("tests/test_files/grid_spectroscopy.3ds").unwrap();
+    let entries = saved_npz("spm-rs-test-3ds.npz", &grid);
+    let (_, data) = entry(&entries, "start_time");
+    assert_eq!(string_data(data), "2021-03-12T14:25:01");
+    let (_, data) = entry(&entries, "xcenter");
+    assert_eq!(f64_data(data), vec![grid.xcenter]);
+}
+
+#[test]
+fn test_npz_names() {
+    let arrays: Vec<_> = ["x", "params", "x", "I/V", "", "\u{c5}", "x_1", "x"]
+        .iter()
+        .map(|name| (name.to_string(), NpyArray::scalar(0.0)))
+        .collect();
+    assert_eq!(
+        npz_names(&arrays),
+        vec!["x", "params", "x_1", "I_V", "arr_4", "\u{c5}", "x_1_1", "x_2"]
+    );
+
+    let out = std::env::temp_dir().join("spm-rs-test-names.npz");
+    write_npz(out.to_str().unwrap(), &arrays).unwrap();
+    let bytes = std::fs::read(&out).unwrap();
+    std::fs::remove_file(&out).unwrap();
+    let names: Vec<_> = parse_npz(&bytes)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names.len(), arrays.len());
+    assert!(names.contains(&"\u{c5}.npy".to_string()));
+}
+
+#[test]
+fn test_npz_too_many_entries() {
+    let arrays: Vec<_> = (0..=u16::MAX as usize)
+        .map(|i| (format!("a{}", i), NpyArray::scalar(0.0)))
+        .collect();
+    let out = std::env::temp_dir().join("spm-rs-test-many.npz");
+    let err = write_npz(out.to_str().unwrap(), &arrays).unwrap_err();
+    assert!(!out.exists());
+    assert_eq!(
+        err.to_string(),
+        "65536 arrays exceed the 65535 entries of a zip file"
+    );
+}
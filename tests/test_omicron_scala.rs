@@ -0,0 +1,56 @@
+use spm_rs::omicron_scala::read_scala;
+
+const SCALA: &str = "tests/test_files/scala/scala.par";
+
+#[test]
+fn test_parameters() {
+    let scala = read_scala(SCALA).unwrap();
+    assert_eq!(scala.img_id, "scala");
+    assert_eq!(scala.date, "14:30:12 10.1.2005");
+    assert_eq!(scala.comment, "Synthetic test image");
+    assert_eq!((scala.xres, scala.yres), (4, 3));
+    assert_eq!((scala.xsize, scala.ysize), (20.0, 15.0));
+    assert_eq!((scala.xoffset, scala.yoffset), (1.5, -2.0));
+    assert_eq!(scala.scan_angle, 30.0);
+    assert_eq!(scala.bias, 1.2);
+    assert_eq!(scala.current, 0.5);
+    assert_eq!(scala.header["Loop Gain"], "1.500000");
+}
+
+#[test]
+fn test_channels() {
+    let scala = read_scala(SCALA).unwrap();
+    // The spectroscopy channel is not an image
+    let files: Vec<_> = scala
+        .channel_info
+        .iter()
+        .map(|c| c.filename.as_str())
+        .collect();
+    assert_eq!(files, vec!["scala.tf0", "scala.tb0", "scala.tf1"]);
+    assert_eq!(scala.channel_info[0].display_name, "Constant Current");
+    assert_eq!(scala.channel_info[1].direction, "Backward");
+    assert_eq!(
+        (scala.channel_info[2].min_raw, scala.channel_info[2].max_raw),
+        (-2048.0, 2047.0)
+    );
+    assert_eq!(scala.channel_info[2].resolution, 0.024414);
+
+    let ids: Vec<_> = scala.channels.iter().map(|c| c.img_id.as_str()).collect();
+    assert_eq!(ids, vec!["scala_Z_fwd", "scala_Z_bwd", "scala_I_fwd"]);
+}
+
+#[test]
+fn test_scaling() {
+    let scala = read_scala(SCALA).unwrap();
+
+    let z_fwd = &scala.channels[0];
+    assert_eq!(z_fwd.z_unit, "nm");
+    assert!((z_fwd.img_data[5] - 5000.0 * 400.0 / 65534.0).abs() < 1e-12);
+    let z_bwd = &scala.channels[1];
+    assert!((z_bwd.img_data[5] + 5000.0 * 400.0 / 65534.0).abs() < 1e-12);
+
+    let current = &scala.channels[2];
+    assert_eq!(current.z_unit, "nA");
+    assert!((current.img_data[0] + 50.0).abs() < 1e-12);
+    assert!((current.img_data[11] - (-50.0 + 1100.0 * 100.0 / 4095.0)).abs() < 1e-12);
+}
@@ -0,0 +1,30 @@
+use spm_rs::park_tiff::read_park_tiff;
+
+const PARK: &str = "tests/test_files/park.tiff";
+
+#[test]
+fn test_header() {
+    let park = read_park_tiff(PARK).unwrap();
+    assert_eq!(park.source_name, "Topography");
+    assert_eq!(park.image_mode, "NCM");
+    assert_eq!((park.xres, park.yres), (4, 3));
+    assert_eq!((park.xsize, park.ysize), (2000.0, 1500.0));
+    assert_eq!((park.xoffset, park.yoffset), (250.0, -500.0));
+    assert_eq!(park.scan_angle, 15.0);
+    assert_eq!(park.set_point, 12.5);
+    assert_eq!(park.set_point_unit, "nm");
+    assert_eq!(park.sample_bias, 0.3);
+    assert!(park.forward);
+}
+
+#[test]
+fn test_data() {
+    let park = read_park_tiff(PARK).unwrap();
+    let img = &park.img_data;
+    assert_eq!(img.img_id, "park_Topography_fwd");
+    assert_eq!((img.xres, img.yres), (4, 3));
+    assert_eq!(img.z_unit, "\u{b5}m");
+    // Calibrated with the data gain instead of the 8 bit preview, bottom line first
+    assert!((img.img_data[8] + 5.0).abs() < 1e-12);
+    assert!((img.img_data[3] - 6.0).abs() < 1e-12);
+}
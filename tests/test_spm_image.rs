@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+
+use spm_rs::mulfile::read_mul;
+use spm_rs::spm_image::{SpmImage, TiffSampleFormat};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+
+fn description(decoder: &mut Decoder<File>) -> BTreeMap<String, String> {
+    decoder
+        .get_tag_ascii_string(Tag::ImageDescription)
+        .unwrap()
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_save_tiff_float() {
+    let mulfile = read_mul("tests/test_files/stm-aarhus-mul-a.mul").unwrap();
+    let img = &mulfile[0].img_data;
+    let metadata = BTreeMap::from([("bias".to_string(), "-0.5 V".to_string())]);
+
+    let out = std::env::temp_dir().join("spm-rs-test-save.tiff");
+    img.save_tiff(out.to_str().unwrap(), TiffSampleFormat::Float32, &metadata)
+        .unwrap();
+    let mut decoder = Decoder::new(File::open(&out).unwrap()).unwrap();
+
+    assert_eq!(
+        decoder.dimensions().unwrap(),
+        (img.xres as u32, img.yres as u32)
+    );
+    let info = description(&mut decoder);
+    assert_eq!(info["unit"], "nm");
+    assert_eq!(info["img_id"], img.img_id);
+    assert_eq!(info["z_unit"], img.z_unit);
+    assert_eq!(info["bias"], "-0.5 V");
+
+    // Pixels per nm
+    let resolution = match decoder.get_tag(Tag::XResolution).unwrap() {
+        tiff::decoder::ifd::Value::Rational(n, d) => n as f64 / d as f64,
+        value => panic!("Unexpected resolution {:?}", value),
+    };
+    assert!((resolution - img.xres as f64 / img.xsize).abs() < 1e-5);
+
+    let data = match decoder.read_image().unwrap() {
+        DecodingResult::F32(data) => data,
+        _ => panic!("Expected float data"),
+    };
+    std::fs::remove_file(&out).unwrap();
+    for (read, value) in data.iter().zip(img.img_data.iter()) {
+        assert_eq!(*read, *value as f32);
+    }
+}
+
+#[test]
+fn test_save_tiff_uint16() {
+    let mulfile = read_mul("tests/test_files/stm-aarhus-mul-a.mul").unwrap();
+    let img = &mulfile[0].img_data;
+
+    let out = std::env::temp_dir().join("spm-rs-test-save-16.tiff");
+    img.save_tiff(
+        out.to_str().unwrap(),
+        TiffSampleFormat::Uint16,
+        &BTreeMap::new(),
+    )
+    .unwrap();
+    let mut decoder = Decoder::new(File::open(&out).unwrap()).unwrap();
+
+    let info = description(&mut decoder);
+    let c0: f64 = info["c0"].parse().unwrap();
+    let c1: f64 = info["c1"].parse().unwrap();
+    assert_eq!(info["vunit"], img.z_unit);
+
+    let data = match decoder.read_image().unwrap() {
+        DecodingResult::U16(data) => data,
+        _ => panic!("Expected 16 bit data"),
+    };
+    std::fs::remove_file(&out).unwrap();
+    assert_eq!(data.iter().min(), Some(&0));
+    assert_eq!(data.iter().max(), Some(&65535));
+    for (read, value) in data.iter().zip(img.img_data.iter()) {
+        assert!((c0 + c1 * f64::from(*read) - value).abs() <= c1 / 2.0 + 1e-9);
+    }
+}
+
+#[test]
+fn test_save_tiff_not_finite() {
+    let img = SpmImage {
+        img_id: "nan".to_string(),
+        xres: 2,
+        yres: 2,
+        xsize: 1.0,
+        ysize: 1.0,
+        z_unit: "nm".to_string(),
+        img_data: vec![f64::NAN, f64::INFINITY, f64::NEG_INFINITY, f64::NAN],
+    };
+    let out = std::env::temp_dir().join("spm-rs-test-save-nan.tiff");
+
+    img.save_tiff(
+        out.to_str().unwrap(),
+        TiffSampleFormat::Float32,
+        &BTreeMap::new(),
+    )
+    .unwrap();
+    let mut decoder = Decoder::new(File::open(&out).unwrap()).unwrap();
+    let info = description(&mut decoder);
+    assert_eq!((info["min"].as_str(), info["max"].as_str()), ("0", "0"));
+
+    img.save_tiff(
+        out.to_str().unwrap(),
+        TiffSampleFormat::Uint16,
+        &BTreeMap::new(),
+    )
+    .unwrap();
+    let mut decoder = Decoder::new(File::open(&out).unwrap()).unwrap();
+    let info = description(&mut decoder);
+    assert_eq!((info["c0"].as_str(), info["c1"].as_str()), ("0", "1"));
+    let data = match decoder.read_image().unwrap() {
+        DecodingResult::U16(data) => data,
+        _ => panic!("Expected 16 bit data"),
+    };
+    std::fs::remove_file(&out).unwrap();
+    assert_eq!(data, vec![0; 4]);
+}
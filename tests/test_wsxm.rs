@@ -0,0 +1,62 @@
+use spm_rs::wsxm::{read_wsxm_curve, read_wsxm_image};
+
+const WSXM_TOP: &str = "tests/test_files/wsxm_image.top";
+const WSXM_STP: &str = "tests/test_files/wsxm_image.stp";
+const WSXM_CUR: &str = "tests/test_files/wsxm_curve.cur";
+
+#[test]
+fn test_header() {
+    let img = read_wsxm_image(WSXM_TOP).unwrap();
+    assert_eq!(img.header["Miscellaneous"]["Comments"], "test image");
+    assert_eq!(img.channel, "Topography");
+    assert_eq!(img.bias, -0.25);
+    assert_eq!((img.set_point, img.set_point_unit.as_str()), (1.5, "nA"));
+    assert_eq!(img.rotation, 15.0);
+}
+
+#[test]
+fn test_sizes() {
+    let img = read_wsxm_image(WSXM_TOP).unwrap();
+    assert_eq!((img.xres, img.yres), (3, 2));
+    // Amplitudes given in Angstrom and micrometer
+    assert_eq!(img.xsize, 2.0);
+    assert_eq!(img.ysize, 10.0);
+    assert_eq!((img.xoffset, img.yoffset), (5.0, -3.0));
+}
+
+#[test]
+fn test_double_data() {
+    let img = read_wsxm_image(WSXM_TOP).unwrap();
+    assert_eq!(img.img_data.z_unit, "nm");
+    // The first row in the file is the bottom one
+    assert_eq!(img.img_data.img_data, vec![3.0, 4.0, 5.0, 0.0, 1.0, 2.0]);
+}
+
+#[test]
+fn test_short_data() {
+    let img = read_wsxm_image(WSXM_STP).unwrap();
+    assert_eq!(img.img_data.z_unit, "nA");
+    // 4 nA over the full range of 16 bit
+    let data = &img.img_data.img_data;
+    assert_eq!(data[1], -2.0);
+    assert_eq!(data[2], 0.5);
+    assert_eq!(data[4], 1.0);
+}
+
+#[test]
+fn test_curve() {
+    let cur = read_wsxm_curve(WSXM_CUR).unwrap();
+    assert_eq!(cur.header["General Info"]["Number of points"], "3");
+    let spectrum = &cur.spectrum;
+    assert_eq!(spectrum.spec_id, "wsxm_curve");
+    assert_eq!(
+        (spectrum.x_label.as_str(), spectrum.x_unit.as_str()),
+        ("Voltage", "V")
+    );
+    assert_eq!(spectrum.x_data, vec![-1.0, 0.0, 1.0]);
+
+    let names: Vec<_> = spectrum.channels.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["Current 1", "Current 2"]);
+    assert_eq!(spectrum.channels[0].unit, "nA");
+    assert_eq!(spectrum.channels[1].data, vec![-0.4, 0.1, 0.6]);
+}